@@ -0,0 +1,218 @@
+use crate::data::Color;
+use serde::{Deserialize, Serialize};
+
+pub const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+/** Longest base time or increment a time control may have. */
+pub const MAX_TIME_CONTROL_MS: u64 = DAY_MS;
+/** Most days a correspondence game may give for a move. */
+pub const MAX_DAYS_PER_MOVE: u32 = 14;
+
+/** How the time added after each move is counted.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum IncrementKind {
+    /** The increment is always added. */
+    #[default]
+    Fischer,
+    /** Gives back the time spent on the move, up to the increment, so a clock never goes up. */
+    Bronstein,
+}
+
+/** Base time per player and what each move adds back, in milliseconds.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct TimeControl {
+    pub base_ms: u64,
+    #[serde(default)]
+    pub increment_ms: u64,
+    #[serde(default)]
+    pub kind: IncrementKind,
+}
+
+impl TimeControl {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.base_ms == 0 {
+            return Err("The base time must be positive".to_string());
+        }
+        if self.base_ms > MAX_TIME_CONTROL_MS || self.increment_ms > MAX_TIME_CONTROL_MS {
+            return Err("The base time and the increment are at most a day".to_string());
+        }
+        Ok(())
+    }
+}
+
+/** Both players' remaining time as of the last move, and since when the side to move has been
+ * thinking. Times are in milliseconds, `running_since` is a Unix timestamp in milliseconds.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Clock {
+    pub control: TimeControl,
+    pub white_ms: u64,
+    pub black_ms: u64,
+    /** `None` until both players are seated, and again once the game is over. */
+    pub running_since: Option<u64>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Self {
+            control,
+            white_ms: control.base_ms,
+            black_ms: control.base_ms,
+            running_since: None,
+        }
+    }
+
+    /** Time `color` has left at `now`, counting the time spent so far when it is `turn`'s move.
+     */
+    pub fn remaining(&self, color: Color, turn: Color, now: u64) -> u64 {
+        let stored = match color {
+            Color::White => self.white_ms,
+            Color::Black => self.black_ms,
+        };
+        match self.running_since {
+            Some(since) if color == turn => stored.saturating_sub(now.saturating_sub(since)),
+            _ => stored,
+        }
+    }
+
+    pub fn start(&mut self, now: u64) {
+        if self.running_since.is_none() {
+            self.running_since = Some(now);
+        }
+    }
+
+    pub fn stop(&mut self, turn: Color, now: u64) {
+        if self.running_since.is_some() {
+            let left = self.remaining(turn, turn, now);
+            *self.time_mut(turn) = left;
+            self.running_since = None;
+        }
+    }
+
+    /** Ends `mover`'s turn at `now`: takes the time spent, adds the increment and starts the
+     * opponent's clock. Does nothing while the clock isn't running.
+     */
+    pub fn press(&mut self, mover: Color, now: u64) {
+        let Some(since) = self.running_since else {
+            return;
+        };
+        let spent = now.saturating_sub(since);
+        let left = self.remaining(mover, mover, now);
+        let added = match self.control.kind {
+            IncrementKind::Fischer => self.control.increment_ms,
+            IncrementKind::Bronstein => spent.min(self.control.increment_ms),
+        };
+        *self.time_mut(mover) = left.saturating_add(added);
+        self.running_since = Some(now);
+    }
+
+    fn time_mut(&mut self, color: Color) -> &mut u64 {
+        match color {
+            Color::White => &mut self.white_ms,
+            Color::Black => &mut self.black_ms,
+        }
+    }
+}
+
+/** Slow games where each move must be played within a number of days. A missed deadline doesn't
+ * end the game by itself, the opponent has to claim the win.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Correspondence {
+    pub days_per_move: u32,
+    /** Unix timestamp in milliseconds by which the side to move has to play, `None` until both
+     * players are seated and once the game is over.
+     */
+    pub deadline: Option<u64>,
+}
+
+impl Correspondence {
+    pub fn new(days_per_move: u32) -> Result<Self, String> {
+        if days_per_move == 0 {
+            return Err("A move needs at least a day".to_string());
+        }
+        if days_per_move > MAX_DAYS_PER_MOVE {
+            return Err(format!("A move gets at most {MAX_DAYS_PER_MOVE} days"));
+        }
+        Ok(Self {
+            days_per_move,
+            deadline: None,
+        })
+    }
+
+    /** Gives the side to move its days, counting from `now`.
+     */
+    pub fn restart(&mut self, now: u64) {
+        self.deadline = Some(now + self.days_per_move as u64 * DAY_MS);
+    }
+
+    pub fn is_missed(&self, now: u64) -> bool {
+        self.deadline.is_some_and(|deadline| now > deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(base_ms: u64, increment_ms: u64) -> TimeControl {
+        TimeControl {
+            base_ms,
+            increment_ms,
+            kind: IncrementKind::Fischer,
+        }
+    }
+
+    #[test]
+    fn validate_caps_base_and_increment() {
+        assert!(control(0, 0).validate().is_err());
+        assert!(control(DAY_MS, DAY_MS).validate().is_ok());
+        assert!(control(DAY_MS + 1, 0).validate().is_err());
+        assert!(control(60_000, DAY_MS + 1).validate().is_err());
+        assert!(control(60_000, u64::MAX).validate().is_err());
+    }
+
+    #[test]
+    fn correspondence_caps_the_days_per_move() {
+        assert!(Correspondence::new(0).is_err());
+        assert!(Correspondence::new(1).is_ok());
+        assert!(Correspondence::new(MAX_DAYS_PER_MOVE).is_ok());
+        assert!(Correspondence::new(MAX_DAYS_PER_MOVE + 1).is_err());
+        assert!(Correspondence::new(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn press_adds_the_increment() {
+        let mut clock = Clock::new(control(60_000, 2_000));
+        clock.start(1_000);
+        clock.press(Color::White, 6_000);
+        assert_eq!(clock.white_ms, 57_000);
+        assert_eq!(clock.remaining(Color::Black, Color::Black, 7_000), 59_000);
+    }
+
+    #[test]
+    fn bronstein_never_gives_back_more_than_spent() {
+        let mut clock = Clock::new(TimeControl {
+            kind: IncrementKind::Bronstein,
+            ..control(60_000, 5_000)
+        });
+        clock.start(0);
+        clock.press(Color::White, 2_000);
+        assert_eq!(clock.white_ms, 60_000);
+        clock.press(Color::Black, 12_000);
+        assert_eq!(clock.black_ms, 55_000);
+    }
+
+    #[test]
+    fn press_saturates_instead_of_overflowing() {
+        let mut clock = Clock {
+            control: control(1, u64::MAX),
+            white_ms: u64::MAX - 1,
+            black_ms: 1,
+            running_since: Some(0),
+        };
+        clock.press(Color::White, 0);
+        assert_eq!(clock.white_ms, u64::MAX);
+    }
+}
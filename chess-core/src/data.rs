@@ -0,0 +1,762 @@
+use serde::{Deserialize, Serialize};
+
+/** (file, rank), both from 0 to 7: (0, 0) is a1 and (7, 7) is h8.
+ */
+pub type Square = (u8, u8);
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Color {
+    #[default]
+    White,
+    Black,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    /** Piece a pawn reaching the last rank turns into, `None` for every other move.
+     */
+    #[serde(default)]
+    pub promotion: Option<PieceKind>,
+}
+
+impl Move {
+    pub fn new(from: Square, to: Square) -> Self {
+        Self {
+            from,
+            to,
+            promotion: None,
+        }
+    }
+}
+
+/** Indexed as `board[file][rank]`, see [`Square`].
+ */
+pub type Board = Vec<Vec<Option<(PieceKind, Color)>>>;
+
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug, Default)]
+pub struct ChessBoard {
+    pub board: Board,
+    /** Moves played since the position the board was loaded from.
+     */
+    pub moves: Vec<Move>,
+    pub selected: Option<Square>,
+    pub turn: Color,
+    pub game_over: bool,
+    pub castling: CastlingRights,
+    /** Square a pawn skipped over with a double step on the previous move, where it can be taken en passant.
+     */
+    pub en_passant: Option<Square>,
+    /** Half moves since the last capture or pawn move, for the fifty-move rule.
+     */
+    pub halfmove_clock: u32,
+    /** Starts at 1 and goes up after each move of black.
+     */
+    pub fullmove_number: u32,
+    /** Hashes of every position reached before the current one, for repetitions.
+     */
+    pub history: Vec<u64>,
+}
+
+/** Which castles are still allowed, i.e. neither the king nor the rook involved has moved
+ * (or been captured) yet. Doesn't say anything about the castle being possible right now.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
+
+impl CastlingRights {
+    pub fn all() -> Self {
+        Self {
+            white_king_side: true,
+            white_queen_side: true,
+            black_king_side: true,
+            black_queen_side: true,
+        }
+    }
+
+    pub fn get(&self, color: Color, king_side: bool) -> bool {
+        match (color, king_side) {
+            (Color::White, true) => self.white_king_side,
+            (Color::White, false) => self.white_queen_side,
+            (Color::Black, true) => self.black_king_side,
+            (Color::Black, false) => self.black_queen_side,
+        }
+    }
+
+    pub fn revoke(&mut self, color: Color, king_side: bool) {
+        match (color, king_side) {
+            (Color::White, true) => self.white_king_side = false,
+            (Color::White, false) => self.white_queen_side = false,
+            (Color::Black, true) => self.black_king_side = false,
+            (Color::Black, false) => self.black_queen_side = false,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl ChessBoard {
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            moves: vec![],
+            selected: None,
+            turn: Color::White,
+            game_over: false,
+            castling: CastlingRights::all(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            history: vec![],
+        }
+    }
+
+    pub fn select(&mut self, square: Square) {
+        self.selected = Some(square);
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<(PieceKind, Color)> {
+        self.board[square.0 as usize][square.1 as usize]
+    }
+
+    /** Legal destination squares of the piece on `square`: pseudo-legal moves that don't land
+     * on a friendly piece and don't leave the mover's king in check.
+     * Doesn't check if the color is the right one (i.e. whose turn it is).
+     */
+    pub fn get_moves(&self, square: Square) -> Vec<Square> {
+        let (kind, color) = match self.piece_at(square) {
+            Some(piece) => piece,
+            None => return vec![],
+        };
+        let mut moves: Vec<Square> = self
+            .get_pseudo_moves(square)
+            .into_iter()
+            .filter(|to| {
+                let mut next = self.clone();
+                next.apply(Move::new(square, *to));
+                !next.is_in_check(color)
+            })
+            .collect();
+        if kind == PieceKind::King {
+            moves.append(&mut self.get_castling_moves(square, color));
+        }
+        moves
+    }
+
+    /** Legal moves of the piece on `square`, with one move per promotion piece when a pawn
+     * reaches the last rank. Like [`ChessBoard::get_moves`], ignores whose turn it is.
+     */
+    pub fn get_piece_moves(&self, square: Square) -> Vec<Move> {
+        let mut moves = vec![];
+        for to in self.get_moves(square) {
+            if self.is_promotion(square, to) {
+                for kind in PROMOTION_PIECES {
+                    moves.push(Move {
+                        promotion: Some(kind),
+                        ..Move::new(square, to)
+                    });
+                }
+            } else {
+                moves.push(Move::new(square, to));
+            }
+        }
+        moves
+    }
+
+    /** Every legal move of the side to move.
+     */
+    pub fn get_all_moves(&self) -> Vec<Move> {
+        let mut moves = vec![];
+        for x in 0..8 {
+            for y in 0..8 {
+                if matches!(self.piece_at((x, y)), Some((_, color)) if color == self.turn) {
+                    moves.append(&mut self.get_piece_moves((x, y)));
+                }
+            }
+        }
+        moves
+    }
+
+    /** Whether `mv` can be played by the side to move, promotion piece included.
+     */
+    pub fn is_legal(&self, mv: &Move) -> bool {
+        let (from, to) = (mv.from, mv.to);
+        if from.0 > 7 || from.1 > 7 || to.0 > 7 || to.1 > 7 {
+            return false;
+        }
+        match self.piece_at(from) {
+            Some((_, color)) if color == self.turn => self.get_piece_moves(from).contains(mv),
+            _ => false,
+        }
+    }
+
+    /** Whether moving the piece on `from` to `to` is a pawn reaching the last rank.
+     */
+    pub fn is_promotion(&self, from: Square, to: Square) -> bool {
+        matches!(self.piece_at(from), Some((PieceKind::Pawn, _))) && (to.1 == 0 || to.1 == 7)
+    }
+
+    /** Plays a move without validating it and gives the turn to the other side.
+     * A king moving two files castles, so the rook is brought over as well, and a pawn moving
+     * to the en passant square takes the pawn that double stepped past it.
+     * A pawn reaching the last rank without a promotion piece becomes a queen.
+     */
+    pub fn move_piece(&mut self, mv: Move) {
+        let kind = match self.piece_at(mv.from) {
+            Some((kind, _)) => kind,
+            None => return,
+        };
+        let capture =
+            self.piece_at(mv.to).is_some() || (kind == PieceKind::Pawn && mv.from.0 != mv.to.0);
+        if kind == PieceKind::Pawn || capture {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.turn == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.history.push(self.position_hash());
+        self.moves.push(mv);
+        self.apply(mv);
+    }
+
+    /** How many times the current position has occurred, itself included.
+     */
+    pub fn repetitions(&self) -> usize {
+        let current = self.position_hash();
+        self.history.iter().filter(|hash| **hash == current).count() + 1
+    }
+
+    /** Whether neither side can possibly mate: kings alone, a single minor piece, or only
+     * bishops all standing on squares of the same color.
+     */
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut knights = 0;
+        let mut bishop_square_colors = vec![];
+        for x in 0..8 {
+            for y in 0..8 {
+                match self.piece_at((x, y)) {
+                    None | Some((PieceKind::King, _)) => {}
+                    Some((PieceKind::Knight, _)) => knights += 1,
+                    Some((PieceKind::Bishop, _)) => bishop_square_colors.push((x + y) % 2),
+                    Some(_) => return false,
+                }
+            }
+        }
+        match (knights, bishop_square_colors.len()) {
+            (0, 0) | (1, 0) => true,
+            (0, _) => bishop_square_colors
+                .windows(2)
+                .all(|pair| pair[0] == pair[1]),
+            _ => false,
+        }
+    }
+
+    /** Whether `color` has more than a lone king or a king and a single minor piece, what it
+     * takes to win on time.
+     */
+    pub fn has_mating_material(&self, color: Color) -> bool {
+        let mut minors = 0;
+        for x in 0..8 {
+            for y in 0..8 {
+                match self.piece_at((x, y)) {
+                    Some((PieceKind::King, _)) | None => {}
+                    Some((_, piece_color)) if piece_color != color => {}
+                    Some((PieceKind::Knight | PieceKind::Bishop, _)) => minors += 1,
+                    Some(_) => return true,
+                }
+            }
+        }
+        minors >= 2
+    }
+
+    /** Hash of what makes two positions the same for repetitions: placement, side to move,
+     * castling rights and a capturable en passant square. Stable across runs (FNV-1a).
+     */
+    pub fn position_hash(&self) -> u64 {
+        let mut bytes = Vec::with_capacity(70);
+        for file in &self.board {
+            for square in file {
+                bytes.push(match square {
+                    None => 0,
+                    Some((kind, color)) => 1 + *kind as u8 + 6 * *color as u8,
+                });
+            }
+        }
+        bytes.push(self.turn as u8);
+        bytes.push(
+            self.castling.white_king_side as u8
+                | (self.castling.white_queen_side as u8) << 1
+                | (self.castling.black_king_side as u8) << 2
+                | (self.castling.black_queen_side as u8) << 3,
+        );
+        let capturable = self.en_passant.filter(|(x, y)| {
+            let pawn_rank = match self.turn {
+                Color::White => *y as i8 - 1,
+                Color::Black => *y as i8 + 1,
+            };
+            [-1, 1].into_iter().any(|dx| {
+                offset((*x, pawn_rank as u8), dx, 0)
+                    .is_some_and(|from| self.piece_at(from) == Some((PieceKind::Pawn, self.turn)))
+            })
+        });
+        if let Some((x, y)) = capturable {
+            bytes.push(x);
+            bytes.push(y);
+        }
+        bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    /** Plays the move on the board itself, without the bookkeeping of [`ChessBoard::move_piece`].
+     */
+    fn apply(&mut self, mv: Move) {
+        let (from, to) = (mv.from, mv.to);
+        let (kind, color) = match self.piece_at(from) {
+            Some(piece) => piece,
+            None => return,
+        };
+        if kind == PieceKind::King {
+            if to.0 == from.0 + 2 {
+                self.relocate((7, from.1), (5, from.1));
+            } else if to.0 + 2 == from.0 {
+                self.relocate((0, from.1), (3, from.1));
+            }
+            self.castling.revoke(color, true);
+            self.castling.revoke(color, false);
+        }
+        let mut en_passant = None;
+        if kind == PieceKind::Pawn {
+            if Some(to) == self.en_passant && from.0 != to.0 {
+                self.board[to.0 as usize][from.1 as usize] = None;
+            }
+            if from.1.abs_diff(to.1) == 2 {
+                en_passant = Some((from.0, (from.1 + to.1) / 2));
+            }
+        }
+        self.en_passant = en_passant;
+        // A rook leaving its corner, or anything landing there, ends castling on that side.
+        for square in [from, to] {
+            match square {
+                (0, 0) => self.castling.revoke(Color::White, false),
+                (7, 0) => self.castling.revoke(Color::White, true),
+                (0, 7) => self.castling.revoke(Color::Black, false),
+                (7, 7) => self.castling.revoke(Color::Black, true),
+                _ => {}
+            }
+        }
+        self.relocate(from, to);
+        if kind == PieceKind::Pawn && (to.1 == 0 || to.1 == 7) {
+            let promotion = mv.promotion.unwrap_or(PieceKind::Queen);
+            self.board[to.0 as usize][to.1 as usize] = Some((promotion, color));
+        }
+        self.turn = self.turn.opposite();
+    }
+
+    /** State of the side to move: whether it is in check and whether it has any legal move left.
+     */
+    pub fn status(&self) -> BoardStatus {
+        let in_check = self.is_in_check(self.turn);
+        let has_moves = (0..8)
+            .flat_map(|x| (0..8).map(move |y| (x, y)))
+            .filter(
+                |square| matches!(self.piece_at(*square), Some((_, color)) if color == self.turn),
+            )
+            .any(|square| !self.get_moves(square).is_empty());
+        match (in_check, has_moves) {
+            (true, true) => BoardStatus::Check,
+            (true, false) => BoardStatus::Checkmate,
+            (false, false) => BoardStatus::Stalemate,
+            (false, true) => BoardStatus::Ongoing,
+        }
+    }
+
+    pub fn find_king(&self, color: Color) -> Option<Square> {
+        (0..8)
+            .flat_map(|x| (0..8).map(move |y| (x, y)))
+            .find(|square| self.piece_at(*square) == Some((PieceKind::King, color)))
+    }
+
+    pub fn is_in_check(&self, color: Color) -> bool {
+        match self.find_king(color) {
+            Some(king) => self.is_attacked(king, color.opposite()),
+            None => false,
+        }
+    }
+
+    /** Whether any piece of color `by` attacks `square`, regardless of what stands on it.
+     */
+    pub fn is_attacked(&self, square: Square, by: Color) -> bool {
+        // Pawns attack diagonally forward, so look one rank "behind" the square from their side.
+        let pawn_dy = match by {
+            Color::White => -1,
+            Color::Black => 1,
+        };
+        for dx in [-1, 1] {
+            if let Some(from) = offset(square, dx, pawn_dy) {
+                if self.piece_at(from) == Some((PieceKind::Pawn, by)) {
+                    return true;
+                }
+            }
+        }
+        for (dx, dy) in KNIGHT_OFFSETS {
+            if let Some(from) = offset(square, dx, dy) {
+                if self.piece_at(from) == Some((PieceKind::Knight, by)) {
+                    return true;
+                }
+            }
+        }
+        for (dx, dy) in KING_OFFSETS {
+            if let Some(from) = offset(square, dx, dy) {
+                if self.piece_at(from) == Some((PieceKind::King, by)) {
+                    return true;
+                }
+            }
+        }
+        for (directions, slider) in [
+            (LINE_DIRECTIONS, PieceKind::Rook),
+            (DIAGONAL_DIRECTIONS, PieceKind::Bishop),
+        ] {
+            for (dx, dy) in directions {
+                let mut current = square;
+                while let Some(next) = offset(current, dx, dy) {
+                    if let Some((kind, color)) = self.piece_at(next) {
+                        if color == by && (kind == slider || kind == PieceKind::Queen) {
+                            return true;
+                        }
+                        break;
+                    }
+                    current = next;
+                }
+            }
+        }
+        false
+    }
+
+    /** King destinations for the castles `color` still has the right to, when the squares
+     * between king and rook are empty and the king doesn't start, cross or land on an attacked square.
+     */
+    fn get_castling_moves(&self, king: Square, color: Color) -> Vec<Square> {
+        let rank = match color {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+        if king != (4, rank) || self.is_attacked(king, color.opposite()) {
+            return vec![];
+        }
+        let mut moves = vec![];
+        for (king_side, rook_file, empty, safe) in [
+            (true, 7, &[5, 6][..], &[5, 6][..]),
+            (false, 0, &[1, 2, 3][..], &[3, 2][..]),
+        ] {
+            if !self.castling.get(color, king_side)
+                || self.piece_at((rook_file, rank)) != Some((PieceKind::Rook, color))
+            {
+                continue;
+            }
+            if empty
+                .iter()
+                .any(|file| self.piece_at((*file, rank)).is_some())
+            {
+                continue;
+            }
+            if safe
+                .iter()
+                .any(|file| self.is_attacked((*file, rank), color.opposite()))
+            {
+                continue;
+            }
+            moves.push((safe[safe.len() - 1], rank));
+        }
+        moves
+    }
+
+    /** Destination squares following the movement rules of the piece on `square`, except that
+     * its own king may be left in check.
+     */
+    fn get_pseudo_moves(&self, square: Square) -> Vec<Square> {
+        let (x, y) = square;
+        let (kind, color) = match self.piece_at(square) {
+            Some(piece) => piece,
+            None => return vec![],
+        };
+        match kind {
+            PieceKind::Pawn => {
+                let mut moves = vec![];
+                let (dy, start_rank, en_passant_rank) = match color {
+                    Color::White => (1, 1, 5),
+                    Color::Black => (-1, 6, 2),
+                };
+                if let Some(one) = offset(square, 0, dy) {
+                    if self.piece_at(one).is_none() {
+                        moves.push(one);
+                        if y == start_rank {
+                            let two = (x, (y as i8 + 2 * dy) as u8);
+                            if self.piece_at(two).is_none() {
+                                moves.push(two);
+                            }
+                        }
+                    }
+                }
+                for dx in [-1, 1] {
+                    if let Some(target) = offset(square, dx, dy) {
+                        let capture = matches!(self.piece_at(target), Some((_, c)) if c != color);
+                        let en_passant =
+                            Some(target) == self.en_passant && target.1 == en_passant_rank;
+                        if capture || en_passant {
+                            moves.push(target);
+                        }
+                    }
+                }
+                moves
+            }
+            PieceKind::Knight => self.get_moves_step(square, color, &KNIGHT_OFFSETS),
+            PieceKind::Bishop => self.get_moves_diagonal(square, color),
+            PieceKind::Rook => self.get_moves_line(square, color),
+            PieceKind::Queen => {
+                let mut moves = self.get_moves_line(square, color);
+                moves.append(&mut self.get_moves_diagonal(square, color));
+                moves
+            }
+            PieceKind::King => self.get_moves_step(square, color, &KING_OFFSETS),
+        }
+    }
+
+    fn get_moves_step(&self, from: Square, color: Color, offsets: &[(i8, i8)]) -> Vec<Square> {
+        offsets
+            .iter()
+            .filter_map(|(dx, dy)| offset(from, *dx, *dy))
+            .filter(|to| !matches!(self.piece_at(*to), Some((_, c)) if c == color))
+            .collect()
+    }
+
+    fn get_moves_line(&self, from: Square, color: Color) -> Vec<Square> {
+        self.get_moves_sliding(from, color, &LINE_DIRECTIONS)
+    }
+
+    fn get_moves_diagonal(&self, from: Square, color: Color) -> Vec<Square> {
+        self.get_moves_sliding(from, color, &DIAGONAL_DIRECTIONS)
+    }
+
+    fn get_moves_sliding(
+        &self,
+        from: Square,
+        color: Color,
+        directions: &[(i8, i8)],
+    ) -> Vec<Square> {
+        let mut moves = vec![];
+        for (dx, dy) in directions {
+            let mut current = from;
+            while let Some(next) = offset(current, *dx, *dy) {
+                match self.piece_at(next) {
+                    None => moves.push(next),
+                    Some((_, c)) => {
+                        if c != color {
+                            moves.push(next);
+                        }
+                        break;
+                    }
+                }
+                current = next;
+            }
+        }
+        moves
+    }
+
+    /** Moves whatever stands on `from` to `to`, capturing what was there.
+     */
+    fn relocate(&mut self, from: Square, to: Square) {
+        let piece = self.board[from.0 as usize][from.1 as usize].take();
+        self.board[to.0 as usize][to.1 as usize] = piece;
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum BoardStatus {
+    Ongoing,
+    Check,
+    Checkmate,
+    Stalemate,
+}
+
+impl Color {
+    pub fn opposite(&self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+pub const PROMOTION_PIECES: [PieceKind; 4] = [
+    PieceKind::Queen,
+    PieceKind::Rook,
+    PieceKind::Bishop,
+    PieceKind::Knight,
+];
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (2, 1),
+    (2, -1),
+    (-2, 1),
+    (-2, -1),
+    (1, 2),
+    (1, -2),
+    (-1, 2),
+    (-1, -2),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+const LINE_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIAGONAL_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+/** The square `dx` files and `dy` ranks away from `from`, if it is still on the board.
+ */
+fn offset(from: Square, dx: i8, dy: i8) -> Option<Square> {
+    let x = from.0 as i8 + dx;
+    let y = from.1 as i8 + dy;
+    if (0..8).contains(&x) && (0..8).contains(&y) {
+        Some((x as u8, y as u8))
+    } else {
+        None
+    }
+}
+
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct ChessPiece {
+    pub piece: PieceKind,
+    pub square: Square,
+}
+
+#[derive(Deserialize, PartialEq, Clone, Debug, Default)]
+pub struct ChessSquare {
+    pub square: Square,
+    pub piece: Option<ChessPiece>,
+    pub selected: bool,
+    pub legal: bool,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::STARTING_FEN;
+
+    /** Number of move sequences `depth` plies long, to compare with published counts. */
+    fn perft(board: &ChessBoard, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = board.get_all_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|mv| {
+                let mut next = board.clone();
+                next.move_piece(mv);
+                perft(&next, depth - 1)
+            })
+            .sum()
+    }
+
+    fn assert_perft(fen: &str, counts: &[u64]) {
+        let board = ChessBoard::from_fen(fen).unwrap();
+        for (depth, count) in counts.iter().enumerate() {
+            assert_eq!(
+                perft(&board, depth as u32 + 1),
+                *count,
+                "{fen} at depth {}",
+                depth + 1
+            );
+        }
+    }
+
+    #[test]
+    fn perft_starting_position() {
+        assert_perft(STARTING_FEN, &[20, 400, 8_902]);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2_039],
+        );
+    }
+
+    #[test]
+    fn perft_en_passant_and_pins() {
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2_812],
+        );
+    }
+
+    #[test]
+    fn perft_promotions_and_castling_through_check() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9_467],
+        );
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1_486],
+        );
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let draw = |fen| {
+            ChessBoard::from_fen(fen)
+                .unwrap()
+                .is_insufficient_material()
+        };
+        assert!(draw("8/8/4k3/8/8/3K4/8/8 w - - 0 1"));
+        assert!(draw("8/8/4k3/8/8/3KN3/8/8 w - - 0 1"));
+        // Bishops on b6 and e3 share a square color, on c6 and e3 they don't
+        assert!(draw("8/8/1b2k3/8/8/3KB3/8/8 w - - 0 1"));
+        assert!(!draw("8/8/2b1k3/8/8/3KB3/8/8 w - - 0 1"));
+        assert!(!draw("8/8/4k3/8/8/3K4/4P3/8 w - - 0 1"));
+    }
+
+    #[test]
+    fn threefold_repetition_counts_positions() {
+        let mut board = ChessBoard::from_fen(STARTING_FEN).unwrap();
+        for _ in 0..2 {
+            for (from, to) in [
+                ((6, 0), (5, 2)),
+                ((6, 7), (5, 5)),
+                ((5, 2), (6, 0)),
+                ((5, 5), (6, 7)),
+            ] {
+                board.move_piece(Move::new(from, to));
+            }
+        }
+        assert_eq!(board.repetitions(), 3);
+    }
+}
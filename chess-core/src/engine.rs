@@ -0,0 +1,441 @@
+use crate::data::{ChessBoard, Color, Move, PieceKind, Square};
+use serde::{Deserialize, Serialize};
+
+/** How far the engine may look: the depth iterative deepening goes up to, and the number of
+ * positions it may visit before settling for the last depth it finished. Counted in positions
+ * rather than time so the search behaves the same on every machine and on wasm.
+ */
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct SearchLimits {
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+/** How well the computer plays.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Strength {
+    /** Looks a single move ahead, plus the captures that follow it. */
+    Beginner,
+    Casual,
+    Club,
+    Strong,
+}
+
+impl Strength {
+    pub fn limits(self) -> SearchLimits {
+        let (depth, nodes) = match self {
+            Strength::Beginner => (1, 2_000),
+            Strength::Casual => (2, 10_000),
+            Strength::Club => (3, 40_000),
+            Strength::Strong => (6, 150_000),
+        };
+        SearchLimits { depth, nodes }
+    }
+}
+
+const MATE: i32 = 100_000;
+const INFINITY: i32 = 1_000_000;
+/** Deepest ply killer moves are kept for, quiescence can go further. */
+const MAX_PLY: usize = 64;
+
+/** Best move for the side to move within `limits`, `None` when it has no legal move.
+ */
+pub fn best_move(board: &ChessBoard, limits: SearchLimits) -> Option<Move> {
+    let mut moves = board.get_all_moves();
+    if moves.is_empty() {
+        return None;
+    }
+    let mut search = Search {
+        limits,
+        nodes: 0,
+        stopped: false,
+        killers: vec![[None; 2]; MAX_PLY],
+    };
+    search.order(board, &mut moves, 0);
+    let mut best = moves[0];
+    for depth in 1..=limits.depth.max(1) {
+        let mut alpha = -INFINITY;
+        let mut depth_best = None;
+        for mv in &moves {
+            let score = -search.negamax(&play(board, *mv), depth - 1, 1, -INFINITY, -alpha);
+            if search.stopped {
+                break;
+            }
+            if score > alpha {
+                alpha = score;
+                depth_best = Some(*mv);
+            }
+        }
+        // The previous best move is searched first, so anything that beat it before the
+        // search ran out of nodes is still an improvement.
+        if let Some(mv) = depth_best {
+            best = mv;
+        }
+        if search.stopped || alpha >= MATE - MAX_PLY as i32 {
+            break;
+        }
+        if let Some(index) = moves.iter().position(|mv| *mv == best) {
+            moves[..=index].rotate_right(1);
+        }
+    }
+    Some(best)
+}
+
+/** Score of the position for the side to move, in centipawns: material plus where each piece
+ * stands, the king moving from its shelter to the center once the heavy pieces are gone.
+ */
+pub fn evaluate(board: &ChessBoard) -> i32 {
+    let endgame = is_endgame(board);
+    let mut score = 0;
+    for file in 0..8 {
+        for rank in 0..8 {
+            if let Some((kind, color)) = board.piece_at((file, rank)) {
+                let value = piece_value(kind) + square_bonus(kind, color, (file, rank), endgame);
+                score += if color == board.turn { value } else { -value };
+            }
+        }
+    }
+    score
+}
+
+struct Search {
+    limits: SearchLimits,
+    nodes: u64,
+    /** Set once the node budget is spent, the scores found since are meaningless. */
+    stopped: bool,
+    /** Quiet moves that caused a cutoff, by ply, tried right after the captures. */
+    killers: Vec<[Option<Move>; 2]>,
+}
+
+impl Search {
+    fn negamax(
+        &mut self,
+        board: &ChessBoard,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if !self.visit() {
+            return 0;
+        }
+        if is_draw(board) {
+            return 0;
+        }
+        // Checked before quiescence too, which would score a mate or stalemate as a quiet position
+        let mut moves = board.get_all_moves();
+        if moves.is_empty() {
+            return if board.is_in_check(board.turn) {
+                // Prefer the quickest mate, and the slowest when getting mated
+                -(MATE - ply as i32)
+            } else {
+                0
+            };
+        }
+        if depth == 0 {
+            return self.quiescence(board, alpha, beta);
+        }
+        self.order(board, &mut moves, ply);
+        for mv in moves {
+            let score = -self.negamax(&play(board, mv), depth - 1, ply + 1, -beta, -alpha);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                if !is_capture(board, &mv) {
+                    self.add_killer(ply, mv);
+                }
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    /** Plays out the captures (and queen promotions) so the evaluation never stops in the
+     * middle of an exchange. The side to move may also stand pat.
+     */
+    fn quiescence(&mut self, board: &ChessBoard, mut alpha: i32, beta: i32) -> i32 {
+        if !self.visit() {
+            return 0;
+        }
+        let stand_pat = evaluate(board);
+        if stand_pat >= beta {
+            return beta;
+        }
+        alpha = alpha.max(stand_pat);
+        let mut moves: Vec<Move> = board
+            .get_all_moves()
+            .into_iter()
+            .filter(|mv| is_capture(board, mv) || mv.promotion == Some(PieceKind::Queen))
+            .collect();
+        moves.sort_by_cached_key(|mv| -capture_order(board, mv));
+        for mv in moves {
+            let score = -self.quiescence(&play(board, mv), -beta, -alpha);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    /** Counts a position, `false` once the budget is spent.
+     */
+    fn visit(&mut self) -> bool {
+        if self.nodes >= self.limits.nodes {
+            self.stopped = true;
+            return false;
+        }
+        self.nodes += 1;
+        true
+    }
+
+    /** Captures first, most valuable victim and least valuable attacker first, then the queen
+     * promotions, then the killer moves of this ply, then the rest.
+     */
+    fn order(&self, board: &ChessBoard, moves: &mut [Move], ply: usize) {
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        moves.sort_by_cached_key(|mv| {
+            if is_capture(board, mv) || mv.promotion.is_some() {
+                -(capture_order(board, mv) + 20_000)
+            } else if killers.contains(&Some(*mv)) {
+                -10_000
+            } else {
+                0
+            }
+        });
+    }
+
+    fn add_killer(&mut self, ply: usize, mv: Move) {
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(mv) {
+                killers[1] = killers[0];
+                killers[0] = Some(mv);
+            }
+        }
+    }
+}
+
+fn play(board: &ChessBoard, mv: Move) -> ChessBoard {
+    let mut next = board.clone();
+    next.move_piece(mv);
+    next
+}
+
+/** A draw by rule, counting any repetition since two of them can always be forced into three.
+ */
+fn is_draw(board: &ChessBoard) -> bool {
+    board.halfmove_clock >= 100 || board.repetitions() >= 2 || board.is_insufficient_material()
+}
+
+fn is_capture(board: &ChessBoard, mv: &Move) -> bool {
+    match board.piece_at(mv.from) {
+        Some((PieceKind::Pawn, _)) if mv.from.0 != mv.to.0 => true,
+        _ => board.piece_at(mv.to).is_some(),
+    }
+}
+
+/** Most valuable victim, least valuable attacker, plus what a promotion gains.
+ */
+fn capture_order(board: &ChessBoard, mv: &Move) -> i32 {
+    let attacker = board
+        .piece_at(mv.from)
+        .map_or(0, |(kind, _)| piece_value(kind));
+    let victim = match board.piece_at(mv.to) {
+        Some((kind, _)) => piece_value(kind),
+        // En passant
+        None if is_capture(board, mv) => piece_value(PieceKind::Pawn),
+        None => 0,
+    };
+    let promotion = mv.promotion.map_or(0, piece_value);
+    10 * victim - attacker / 10 + promotion
+}
+
+fn piece_value(kind: PieceKind) -> i32 {
+    match kind {
+        PieceKind::Pawn => 100,
+        PieceKind::Knight => 320,
+        PieceKind::Bishop => 330,
+        PieceKind::Rook => 500,
+        PieceKind::Queen => 900,
+        PieceKind::King => 0,
+    }
+}
+
+/** No queens left, or only a queen and at most one minor piece with it on each side.
+ */
+fn is_endgame(board: &ChessBoard) -> bool {
+    let mut queens = [0; 2];
+    let mut others = [0; 2];
+    for file in 0..8 {
+        for rank in 0..8 {
+            match board.piece_at((file, rank)) {
+                Some((PieceKind::Queen, color)) => queens[color as usize] += 1,
+                Some((PieceKind::Knight | PieceKind::Bishop, color)) => others[color as usize] += 1,
+                Some((PieceKind::Rook, color)) => others[color as usize] += 2,
+                _ => {}
+            }
+        }
+    }
+    (0..2).all(|color| queens[color] == 0 || (queens[color] == 1 && others[color] <= 1))
+}
+
+fn square_bonus(kind: PieceKind, color: Color, square: Square, endgame: bool) -> i32 {
+    let table = match kind {
+        PieceKind::Pawn => &PAWN_TABLE,
+        PieceKind::Knight => &KNIGHT_TABLE,
+        PieceKind::Bishop => &BISHOP_TABLE,
+        PieceKind::Rook => &ROOK_TABLE,
+        PieceKind::Queen => &QUEEN_TABLE,
+        PieceKind::King if endgame => &KING_ENDGAME_TABLE,
+        PieceKind::King => &KING_TABLE,
+    };
+    // The tables read like a diagram from White's side, the 8th rank first
+    let row = match color {
+        Color::White => 7 - square.1 as usize,
+        Color::Black => square.1 as usize,
+    };
+    table[row * 8 + square.0 as usize]
+}
+
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRENGTHS: [Strength; 4] = [
+        Strength::Beginner,
+        Strength::Casual,
+        Strength::Club,
+        Strength::Strong,
+    ];
+
+    fn best_san(fen: &str, strength: Strength) -> Option<String> {
+        let board = ChessBoard::from_fen(fen).unwrap();
+        best_move(&board, strength.limits()).map(|mv| board.to_san(&mv))
+    }
+
+    #[test]
+    fn mates_in_one_at_every_strength() {
+        for strength in STRENGTHS {
+            assert_eq!(
+                best_san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", strength).as_deref(),
+                Some("Ra8#"),
+                "{strength:?}"
+            );
+            assert_eq!(
+                best_san("k7/8/2K5/8/8/8/8/1Q6 w - - 0 1", strength).as_deref(),
+                Some("Qb7#"),
+                "{strength:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_move_when_the_game_is_over() {
+        assert_eq!(
+            best_san("k7/1Q6/2K5/8/8/8/8/8 b - - 0 1", Strength::Club),
+            None
+        );
+        assert_eq!(
+            best_san("k7/8/1QK5/8/8/8/8/8 b - - 0 1", Strength::Club),
+            None
+        );
+    }
+
+    #[test]
+    fn evaluates_for_the_side_to_move() {
+        let white = ChessBoard::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let black = ChessBoard::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+        assert!(evaluate(&white) > 800);
+        assert_eq!(evaluate(&white), -evaluate(&black));
+    }
+}
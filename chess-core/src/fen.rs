@@ -0,0 +1,332 @@
+use crate::data::{Board, CastlingRights, ChessBoard, Color, PieceKind, Square};
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+impl ChessBoard {
+    /** Reads a position in Forsyth-Edwards Notation. The move counters may be left out,
+     * they then default to `0 1`. Impossible positions are refused: not exactly one king per
+     * side, the side that just moved still in check, pawns on the first or last rank, castling
+     * rights whose king or rook has left its square, or an en passant square no pawn just
+     * double stepped past.
+     */
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(format!(
+                "Expected 4 or 6 fields in FEN, found {}",
+                fields.len()
+            ));
+        }
+
+        let mut board: Board = vec![vec![None; 8]; 8];
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(format!("Expected 8 ranks in FEN, found {}", ranks.len()));
+        }
+        for (i, rank) in ranks.iter().enumerate() {
+            let y = 7 - i;
+            let mut x = 0;
+            for c in rank.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    x += empty as usize;
+                } else {
+                    let piece = piece_from_char(c).ok_or(format!("Invalid piece '{c}' in FEN"))?;
+                    if x > 7 {
+                        return Err(format!("Too many squares on rank {}", y + 1));
+                    }
+                    board[x][y] = Some(piece);
+                    x += 1;
+                }
+            }
+            if x != 8 {
+                return Err(format!("Rank {} has {x} squares instead of 8", y + 1));
+            }
+        }
+
+        let mut chess_board = ChessBoard::new(board);
+        chess_board.turn = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            other => return Err(format!("Invalid side to move '{other}'")),
+        };
+        chess_board.castling = CastlingRights::default();
+        if fields[2] != "-" {
+            for c in fields[2].chars() {
+                match c {
+                    'K' => chess_board.castling.white_king_side = true,
+                    'Q' => chess_board.castling.white_queen_side = true,
+                    'k' => chess_board.castling.black_king_side = true,
+                    'q' => chess_board.castling.black_queen_side = true,
+                    other => return Err(format!("Invalid castling right '{other}'")),
+                }
+            }
+        }
+        chess_board.en_passant = match fields[3] {
+            "-" => None,
+            square => {
+                Some(parse_square(square).ok_or(format!("Invalid en passant square '{square}'"))?)
+            }
+        };
+        if fields.len() == 6 {
+            chess_board.halfmove_clock = fields[4]
+                .parse()
+                .map_err(|_| format!("Invalid halfmove clock '{}'", fields[4]))?;
+            chess_board.fullmove_number = fields[5]
+                .parse()
+                .map_err(|_| format!("Invalid fullmove number '{}'", fields[5]))?;
+        }
+
+        for color in [Color::White, Color::Black] {
+            let kings = chess_board
+                .board
+                .iter()
+                .flatten()
+                .filter(|square| **square == Some((PieceKind::King, color)))
+                .count();
+            if kings != 1 {
+                return Err(format!("Expected one {color:?} king, found {kings}"));
+            }
+        }
+        if chess_board.is_in_check(chess_board.turn.opposite()) {
+            return Err("The side that isn't to move is in check".to_string());
+        }
+        for x in 0..8 {
+            for y in [0, 7] {
+                if matches!(chess_board.piece_at((x, y)), Some((PieceKind::Pawn, _))) {
+                    return Err(format!("Pawn on {}", square_name((x, y))));
+                }
+            }
+        }
+        for (color, king_side, right) in [
+            (Color::White, true, 'K'),
+            (Color::White, false, 'Q'),
+            (Color::Black, true, 'k'),
+            (Color::Black, false, 'q'),
+        ] {
+            let rank = match color {
+                Color::White => 0,
+                Color::Black => 7,
+            };
+            let rook_file = if king_side { 7 } else { 0 };
+            if chess_board.castling.get(color, king_side)
+                && (chess_board.piece_at((4, rank)) != Some((PieceKind::King, color))
+                    || chess_board.piece_at((rook_file, rank)) != Some((PieceKind::Rook, color)))
+            {
+                return Err(format!(
+                    "Castling right '{right}' without the king and rook on their squares"
+                ));
+            }
+        }
+        if let Some(square) = chess_board.en_passant {
+            if !chess_board.double_stepped_past(square) {
+                return Err(format!(
+                    "No pawn just double stepped past the en passant square {}",
+                    square_name(square)
+                ));
+            }
+        }
+        Ok(chess_board)
+    }
+
+    /** Whether the opponent's last move can have been a pawn double step over `square`: the
+     * pawn stands right in front of it, and the square and the one the pawn came from are
+     * empty.
+     */
+    fn double_stepped_past(&self, square: Square) -> bool {
+        let (mover, rank, direction) = match self.turn {
+            Color::White => (Color::Black, 5, -1),
+            Color::Black => (Color::White, 2, 1),
+        };
+        let (x, y) = square;
+        let pawn_rank = (y as i8 + direction) as u8;
+        let start_rank = (y as i8 - direction) as u8;
+        y == rank
+            && self.piece_at((x, pawn_rank)) == Some((PieceKind::Pawn, mover))
+            && self.piece_at(square).is_none()
+            && self.piece_at((x, start_rank)).is_none()
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut placement = vec![];
+        for y in (0..8).rev() {
+            let mut rank = String::new();
+            let mut empty = 0;
+            for x in 0..8 {
+                match self.piece_at((x, y)) {
+                    None => empty += 1,
+                    Some(piece) => {
+                        if empty > 0 {
+                            rank.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        rank.push(piece_to_char(piece));
+                    }
+                }
+            }
+            if empty > 0 {
+                rank.push_str(&empty.to_string());
+            }
+            placement.push(rank);
+        }
+
+        let turn = match self.turn {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+        let mut castling = String::new();
+        for (allowed, c) in [
+            (self.castling.white_king_side, 'K'),
+            (self.castling.white_queen_side, 'Q'),
+            (self.castling.black_king_side, 'k'),
+            (self.castling.black_queen_side, 'q'),
+        ] {
+            if allowed {
+                castling.push(c);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        let en_passant = match self.en_passant {
+            Some(square) => square_name(square),
+            None => "-".to_string(),
+        };
+        format!(
+            "{} {turn} {castling} {en_passant} {} {}",
+            placement.join("/"),
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+}
+
+/** Name of a square, e.g. (4, 3) is "e4".
+ */
+pub fn square_name(square: Square) -> String {
+    format!("{}{}", (b'a' + square.0) as char, square.1 + 1)
+}
+
+/** Square from its name, e.g. "e4" is (4, 3).
+ */
+pub fn parse_square(name: &str) -> Option<Square> {
+    let bytes = name.as_bytes();
+    if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1])
+    {
+        return None;
+    }
+    Some((bytes[0] - b'a', bytes[1] - b'1'))
+}
+
+/** Uppercase for white, lowercase for black.
+ */
+pub fn piece_to_char((kind, color): (PieceKind, Color)) -> char {
+    let c = match kind {
+        PieceKind::Pawn => 'p',
+        PieceKind::Knight => 'n',
+        PieceKind::Bishop => 'b',
+        PieceKind::Rook => 'r',
+        PieceKind::Queen => 'q',
+        PieceKind::King => 'k',
+    };
+    match color {
+        Color::White => c.to_ascii_uppercase(),
+        Color::Black => c,
+    }
+}
+
+pub fn piece_from_char(c: char) -> Option<(PieceKind, Color)> {
+    let kind = match c.to_ascii_lowercase() {
+        'p' => PieceKind::Pawn,
+        'n' => PieceKind::Knight,
+        'b' => PieceKind::Bishop,
+        'r' => PieceKind::Rook,
+        'q' => PieceKind::Queen,
+        'k' => PieceKind::King,
+        _ => return None,
+    };
+    let color = if c.is_ascii_uppercase() {
+        Color::White
+    } else {
+        Color::Black
+    };
+    Some((kind, color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Move;
+
+    #[test]
+    fn round_trips() {
+        for fen in [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 40",
+        ] {
+            assert_eq!(ChessBoard::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn accepts_every_setup() {
+        for (name, fen) in crate::setups::SETUPS {
+            assert!(ChessBoard::from_fen(fen).is_ok(), "{name}");
+        }
+        let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        assert!(ChessBoard::from_fen(after_e4).is_ok());
+        assert!(ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").is_ok());
+    }
+
+    #[test]
+    fn counters_default_when_left_out() {
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w - -").unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+    }
+
+    #[test]
+    fn moves_update_the_fen() {
+        let mut board = ChessBoard::from_fen(STARTING_FEN).unwrap();
+        board.move_piece(Move::new((4, 1), (4, 3)));
+        board.move_piece(Move::new((6, 7), (5, 5)));
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2"
+        );
+    }
+
+    #[test]
+    fn refuses_invalid_positions() {
+        for fen in [
+            "",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1",
+            "rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w - - 0 1 extra",
+            // Black is in check with White to move
+            "4k3/4R3/8/8/8/8/8/4K3 w - - 0 1",
+            "kP6/8/8/8/8/8/8/K7 w - - 0 1",
+            "4k3/8/8/8/8/8/8/p3K3 b - - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K1R1 w K - 0 1",
+            "r3k2r/8/8/8/8/8/8/R2K3R w Q - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e4 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/4P3/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e3 0 1",
+        ] {
+            assert!(ChessBoard::from_fen(fen).is_err(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn square_names() {
+        assert_eq!(square_name((0, 0)), "a1");
+        assert_eq!(square_name((7, 7)), "h8");
+        assert_eq!(parse_square("e4"), Some((4, 3)));
+        assert_eq!(parse_square("i1"), None);
+        assert_eq!(parse_square("a9"), None);
+    }
+}
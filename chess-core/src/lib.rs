@@ -0,0 +1,10 @@
+//! Board model, move generation and wire types shared by the client and the server.
+
+pub mod clock;
+pub mod data;
+pub mod engine;
+pub mod fen;
+pub mod notation;
+pub mod pgn;
+pub mod setups;
+pub mod specs;
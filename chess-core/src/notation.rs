@@ -0,0 +1,298 @@
+//! Move notations: Standard Algebraic Notation (`e4`, `Nxf7+`, `O-O`) and the coordinate
+//! notation of the Universal Chess Interface (`e2e4`, `e7e8q`).
+
+use crate::data::{BoardStatus, ChessBoard, Move, PieceKind};
+use crate::fen::{parse_square, square_name};
+
+impl Move {
+    pub fn to_uci(&self) -> String {
+        let mut uci = format!("{}{}", square_name(self.from), square_name(self.to));
+        if let Some(promotion) = self.promotion {
+            uci.push(piece_letter(promotion).to_ascii_lowercase());
+        }
+        uci
+    }
+
+    /** Reads a move in UCI notation. Doesn't check that it is legal anywhere.
+     */
+    pub fn from_uci(uci: &str) -> Result<Move, String> {
+        if !uci.is_ascii() || !(4..=5).contains(&uci.len()) {
+            return Err(format!("Invalid UCI move {uci}"));
+        }
+        let from = parse_square(&uci[0..2]).ok_or(format!("Invalid UCI move {uci}"))?;
+        let to = parse_square(&uci[2..4]).ok_or(format!("Invalid UCI move {uci}"))?;
+        let promotion = match uci[4..].chars().next() {
+            None => None,
+            Some(c) => Some(
+                piece_from_letter(c.to_ascii_uppercase())
+                    .filter(|kind| *kind != PieceKind::King)
+                    .ok_or(format!("Invalid promotion piece in {uci}"))?,
+            ),
+        };
+        Ok(Move {
+            from,
+            to,
+            promotion,
+        })
+    }
+}
+
+impl ChessBoard {
+    /** Reads a move written either in UCI or in SAN notation, legal for the side to move.
+     */
+    pub fn parse_move(&self, text: &str) -> Result<Move, String> {
+        if let Ok(mv) = Move::from_uci(text) {
+            if self.is_legal(&mv) {
+                return Ok(mv);
+            }
+        }
+        self.parse_san(text)
+    }
+
+    /** Standard Algebraic Notation of `mv`, which must be legal in this position, e.g. `Nbd7`,
+     * `exd6`, `e8=Q+` or `O-O`.
+     */
+    pub fn to_san(&self, mv: &Move) -> String {
+        let kind = match self.piece_at(mv.from) {
+            Some((kind, _)) => kind,
+            None => return String::new(),
+        };
+        let mut san = String::new();
+        if kind == PieceKind::King && mv.from.0.abs_diff(mv.to.0) == 2 {
+            san.push_str(if mv.to.0 > mv.from.0 { "O-O" } else { "O-O-O" });
+        } else {
+            let capture =
+                self.piece_at(mv.to).is_some() || (kind == PieceKind::Pawn && mv.from.0 != mv.to.0);
+            if kind == PieceKind::Pawn {
+                if capture {
+                    san.push((b'a' + mv.from.0) as char);
+                }
+            } else {
+                san.push(piece_letter(kind));
+                san.push_str(&self.disambiguation(mv, kind));
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&square_name(mv.to));
+            if let Some(promotion) = mv.promotion {
+                san.push('=');
+                san.push(piece_letter(promotion));
+            }
+        }
+
+        let mut next = self.clone();
+        next.move_piece(*mv);
+        match next.status() {
+            BoardStatus::Check => san.push('+'),
+            BoardStatus::Checkmate => san.push('#'),
+            BoardStatus::Ongoing | BoardStatus::Stalemate => {}
+        }
+        san
+    }
+
+    /** Reads a move in Standard Algebraic Notation, legal for the side to move. Check and
+     * annotation suffixes (`+`, `#`, `!`, `?`) are ignored, `0-0` is accepted for `O-O` and the
+     * `=` before a promotion piece is optional.
+     */
+    pub fn parse_san(&self, san: &str) -> Result<Move, String> {
+        let text = san.trim_end_matches(['+', '#', '!', '?']);
+        let candidates: Vec<Move> = if matches!(text, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+            let king_side = text.len() == 3;
+            self.get_all_moves()
+                .into_iter()
+                .filter(|mv| {
+                    matches!(self.piece_at(mv.from), Some((PieceKind::King, _)))
+                        && mv.from.0.abs_diff(mv.to.0) == 2
+                        && (mv.to.0 > mv.from.0) == king_side
+                })
+                .collect()
+        } else {
+            let mut rest = text;
+            let kind = match rest.chars().next().and_then(piece_from_letter) {
+                Some(kind) => {
+                    rest = &rest[1..];
+                    kind
+                }
+                None => PieceKind::Pawn,
+            };
+            let mut promotion = None;
+            if let Some(last) = rest.chars().last().and_then(piece_from_letter) {
+                promotion = Some(last);
+                rest = rest[..rest.len() - 1].trim_end_matches('=');
+            }
+            if rest.len() < 2 || !rest.is_char_boundary(rest.len() - 2) {
+                return Err(format!("Invalid move {san}"));
+            }
+            let to = parse_square(&rest[rest.len() - 2..])
+                .ok_or(format!("Invalid destination square in {san}"))?;
+            let origin = rest[..rest.len() - 2].trim_end_matches('x');
+            let mut from_file = None;
+            let mut from_rank = None;
+            for c in origin.chars() {
+                match c {
+                    'a'..='h' => from_file = Some(c as u8 - b'a'),
+                    '1'..='8' => from_rank = Some(c as u8 - b'1'),
+                    _ => return Err(format!("Invalid move {san}")),
+                }
+            }
+            self.get_all_moves()
+                .into_iter()
+                .filter(|mv| {
+                    mv.to == to
+                        && mv.promotion == promotion
+                        && matches!(self.piece_at(mv.from), Some((k, _)) if k == kind)
+                        && from_file.is_none_or(|file| mv.from.0 == file)
+                        && from_rank.is_none_or(|rank| mv.from.1 == rank)
+                })
+                .collect()
+        };
+        match candidates[..] {
+            [mv] => Ok(mv),
+            [] => Err(format!("Illegal move {san}")),
+            _ => Err(format!("Ambiguous move {san}")),
+        }
+    }
+
+    /** File, rank or whole square of the origin when another piece of the same kind could
+     * also go to the destination.
+     */
+    fn disambiguation(&self, mv: &Move, kind: PieceKind) -> String {
+        let others: Vec<Move> = self
+            .get_all_moves()
+            .into_iter()
+            .filter(|other| {
+                other.to == mv.to
+                    && other.from != mv.from
+                    && matches!(self.piece_at(other.from), Some((k, _)) if k == kind)
+            })
+            .collect();
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|other| other.from.0 != mv.from.0) {
+            ((b'a' + mv.from.0) as char).to_string()
+        } else if others.iter().all(|other| other.from.1 != mv.from.1) {
+            ((b'1' + mv.from.1) as char).to_string()
+        } else {
+            square_name(mv.from)
+        }
+    }
+}
+
+/** SAN of each of `moves`, played in order from the position `start_fen`. Stops at the first
+ * move that isn't legal.
+ */
+pub fn san_moves(start_fen: &str, moves: &[Move]) -> Vec<String> {
+    let mut board = match ChessBoard::from_fen(start_fen) {
+        Ok(board) => board,
+        Err(_) => return vec![],
+    };
+    let mut sans = vec![];
+    for mv in moves {
+        if !board.is_legal(mv) {
+            break;
+        }
+        sans.push(board.to_san(mv));
+        board.move_piece(*mv);
+    }
+    sans
+}
+
+fn piece_from_letter(letter: char) -> Option<PieceKind> {
+    match letter {
+        'N' => Some(PieceKind::Knight),
+        'B' => Some(PieceKind::Bishop),
+        'R' => Some(PieceKind::Rook),
+        'Q' => Some(PieceKind::Queen),
+        'K' => Some(PieceKind::King),
+        _ => None,
+    }
+}
+
+/** Uppercase letter used for the piece in SAN, pawns have none but get 'P' here.
+ */
+pub fn piece_letter(kind: PieceKind) -> char {
+    match kind {
+        PieceKind::Pawn => 'P',
+        PieceKind::Knight => 'N',
+        PieceKind::Bishop => 'B',
+        PieceKind::Rook => 'R',
+        PieceKind::Queen => 'Q',
+        PieceKind::King => 'K',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::STARTING_FEN;
+
+    fn san(fen: &str, uci: &str) -> String {
+        let board = ChessBoard::from_fen(fen).unwrap();
+        board.to_san(&Move::from_uci(uci).unwrap())
+    }
+
+    #[test]
+    fn every_legal_move_round_trips() {
+        for fen in [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ] {
+            let board = ChessBoard::from_fen(fen).unwrap();
+            for mv in board.get_all_moves() {
+                let san = board.to_san(&mv);
+                assert_eq!(board.parse_san(&san), Ok(mv), "{san} in {fen}");
+                assert_eq!(Move::from_uci(&mv.to_uci()), Ok(mv));
+                assert_eq!(board.parse_move(&mv.to_uci()), Ok(mv));
+            }
+        }
+    }
+
+    #[test]
+    fn writes_san() {
+        assert_eq!(san(STARTING_FEN, "g1f3"), "Nf3");
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(san(kiwipete, "e1g1"), "O-O");
+        assert_eq!(san(kiwipete, "e1c1"), "O-O-O");
+        assert_eq!(san(kiwipete, "e5f7"), "Nxf7");
+        assert_eq!(san(kiwipete, "d5e6"), "dxe6");
+        assert_eq!(san(kiwipete, "c3b5"), "Nb5");
+        assert_eq!(san("4k3/8/8/8/8/2N3N1/8/4K3 w - - 0 1", "c3e4"), "Nce4");
+        assert_eq!(san("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1", "a1d1"), "Rad1");
+        assert_eq!(san("4k3/8/8/8/8/R7/8/R3K3 w - - 0 1", "a3a2"), "R3a2");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+        assert_eq!(san("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), "b8=Q+");
+        assert_eq!(san("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8n"), "b8=N");
+    }
+
+    #[test]
+    fn reads_loose_san() {
+        let board = ChessBoard::from_fen(STARTING_FEN).unwrap();
+        assert_eq!(board.parse_san("Nf3+"), Ok(Move::new((6, 0), (5, 2))));
+        assert_eq!(board.parse_san("e4!?"), Ok(Move::new((4, 1), (4, 3))));
+        assert!(board.parse_san("e5").is_err());
+        assert!(board.parse_san("Nd2").is_err());
+        assert!(board.parse_san("O-O").is_err());
+        let castle = ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(castle.parse_san("0-0"), Ok(Move::new((4, 0), (6, 0))));
+    }
+
+    #[test]
+    fn san_of_a_game() {
+        let moves = [
+            Move::new((4, 1), (4, 3)),
+            Move::new((4, 6), (4, 4)),
+            Move::new((3, 0), (7, 4)),
+            Move::new((1, 7), (2, 5)),
+            Move::new((5, 0), (2, 3)),
+            Move::new((6, 7), (5, 5)),
+            Move::new((7, 4), (5, 6)),
+        ];
+        assert_eq!(
+            san_moves(STARTING_FEN, &moves),
+            ["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"]
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! Portable Game Notation import and export.
+
+use crate::data::{ChessBoard, Color};
+use crate::fen::STARTING_FEN;
+use crate::specs::{ChessGame, GameResult};
+use std::fmt;
+
+const MAX_LINE_LENGTH: usize = 80;
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct PgnError {
+    /** Line of the PGN text the error was found on, starting at 1.
+     */
+    pub line: usize,
+    /** Half move the error is about, starting at 1, `None` when it isn't about a move.
+     */
+    pub ply: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.ply {
+            Some(ply) => write!(f, "line {}, ply {}: {}", self.line, ply, self.message),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+impl ChessGame {
+    /** The game as PGN: the seven tag roster (plus `SetUp`/`FEN` when the game didn't start
+     * from the standard position) followed by the moves in SAN.
+     */
+    pub fn to_pgn(&self) -> String {
+        let (white, black) = match self.color_player_1 {
+            Color::White => (&self.player1, &self.player2),
+            Color::Black => (&self.player2, &self.player1),
+        };
+        let result = match self.result {
+            Some(GameResult::WhiteWins) => "1-0",
+            Some(GameResult::BlackWins) => "0-1",
+            Some(GameResult::Draw) => "1/2-1/2",
+            None => "*",
+        };
+
+        let mut pgn = String::new();
+        for (tag, value) in [
+            ("Event", "Casual game"),
+            ("Site", "rust-chess"),
+            ("Date", &pgn_date(self.created_at)),
+            ("Round", "-"),
+            ("White", or_unknown(white)),
+            ("Black", or_unknown(black)),
+            ("Result", result),
+        ] {
+            pgn.push_str(&format!("[{tag} \"{}\"]\n", escape(value)));
+        }
+        if self.start_fen != STARTING_FEN {
+            pgn.push_str("[SetUp \"1\"]\n");
+            pgn.push_str(&format!("[FEN \"{}\"]\n", self.start_fen));
+        }
+        pgn.push('\n');
+
+        let mut tokens = vec![];
+        if let Ok(mut board) = ChessBoard::from_fen(&self.start_fen) {
+            for (i, mv) in self.board.moves.iter().enumerate() {
+                if board.turn == Color::White {
+                    tokens.push(format!("{}.", board.fullmove_number));
+                } else if i == 0 {
+                    tokens.push(format!("{}...", board.fullmove_number));
+                }
+                tokens.push(board.to_san(mv));
+                board.move_piece(*mv);
+            }
+        }
+        tokens.push(result.to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+}
+
+impl ChessGame {
+    /** Rebuilds a game from the first game of a PGN text by replaying its moves through the
+     * move validator, starting from the `FEN` tag if there is one. Comments, variations and
+     * NAGs are skipped. The game only ends if the moves lead to a mate or a draw, so a game
+     * whose result tag says someone resigned can still be continued.
+     */
+    pub fn from_pgn(text: &str) -> Result<ChessGame, PgnError> {
+        let mut fen = (0, STARTING_FEN.to_string());
+        let mut moves = vec![];
+        let mut in_comment = false;
+        let mut comment_line = 0;
+        // Line each open variation started on, innermost last
+        let mut variations: Vec<usize> = vec![];
+        'lines: for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let trimmed = line.trim();
+            if !in_comment && variations.is_empty() && trimmed.starts_with('[') {
+                if !moves.is_empty() {
+                    // Start of the next game
+                    break;
+                }
+                let (name, value) = parse_tag(trimmed).ok_or(PgnError {
+                    line: line_number,
+                    ply: None,
+                    message: format!("Invalid tag {trimmed}"),
+                })?;
+                if name == "FEN" {
+                    fen = (line_number, value);
+                }
+                continue;
+            }
+
+            let mut token = String::new();
+            for c in line.chars().chain([' ']) {
+                if in_comment {
+                    in_comment = c != '}';
+                    continue;
+                }
+                match c {
+                    '{' | ';' | '(' | ')' => {}
+                    c if !c.is_whitespace() => {
+                        token.push(c);
+                        continue;
+                    }
+                    _ => {}
+                }
+                if variations.is_empty() && !token.is_empty() {
+                    let san = strip_move_number(&token);
+                    if matches!(token.as_str(), "1-0" | "0-1" | "1/2-1/2" | "*") {
+                        break 'lines;
+                    }
+                    if !san.is_empty() && !san.starts_with('$') {
+                        moves.push((line_number, san.to_string()));
+                    }
+                }
+                token.clear();
+                match c {
+                    '{' => {
+                        in_comment = true;
+                        comment_line = line_number;
+                    }
+                    ';' => continue 'lines,
+                    '(' => variations.push(line_number),
+                    ')' => {
+                        variations.pop().ok_or(PgnError {
+                            line: line_number,
+                            ply: None,
+                            message: "Unmatched )".to_string(),
+                        })?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if in_comment {
+            return Err(PgnError {
+                line: comment_line,
+                ply: None,
+                message: "Unclosed comment".to_string(),
+            });
+        }
+        if let Some(line) = variations.pop() {
+            return Err(PgnError {
+                line,
+                ply: None,
+                message: "Unclosed variation".to_string(),
+            });
+        }
+
+        let mut game = ChessGame::new(Color::White);
+        game.load_fen(&fen.1).map_err(|message| PgnError {
+            line: fen.0,
+            ply: None,
+            message,
+        })?;
+        for (ply, (line, san)) in moves.into_iter().enumerate() {
+            let error = |message| PgnError {
+                line,
+                ply: Some(ply + 1),
+                message,
+            };
+            if game.is_over {
+                return Err(error(format!("{san} is played after the end of the game")));
+            }
+            let mv = game.board.parse_san(&san).map_err(error)?;
+            game.move_piece(mv);
+        }
+        Ok(game)
+    }
+}
+
+/** The move of a token, without the move number in front of it (`12.`, `12...`, `...`).
+ * Castling written with zeros (`0-0`) is left alone since no dot follows its digit.
+ */
+fn strip_move_number(token: &str) -> &str {
+    let after_digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    if after_digits.starts_with('.') {
+        after_digits.trim_start_matches('.')
+    } else {
+        token
+    }
+}
+
+/** Name and value of a `[Name "value"]` tag pair.
+ */
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((
+        name.to_string(),
+        value.replace("\\\"", "\"").replace("\\\\", "\\"),
+    ))
+}
+
+fn or_unknown(name: &str) -> &str {
+    if name.is_empty() {
+        "?"
+    } else {
+        name
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/** `YYYY.MM.DD` date of a unix timestamp, `????.??.??` when it is unknown (0).
+ */
+fn pgn_date(timestamp: u64) -> String {
+    if timestamp == 0 {
+        return "????.??.??".to_string();
+    }
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::PieceKind;
+
+    fn plies(pgn: &str) -> usize {
+        ChessGame::from_pgn(pgn).unwrap().board.moves.len()
+    }
+
+    #[test]
+    fn castling_with_zeros() {
+        let pgn = "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 Nf6 5. d3 0-0 *";
+        let game = ChessGame::from_pgn(pgn).unwrap();
+        assert_eq!(game.board.moves.len(), 10);
+        assert_eq!(
+            game.board.piece_at((6, 0)),
+            Some((PieceKind::King, Color::White))
+        );
+        assert_eq!(
+            game.board.piece_at((6, 7)),
+            Some((PieceKind::King, Color::Black))
+        );
+    }
+
+    #[test]
+    fn move_numbers_glued_to_moves() {
+        assert_eq!(plies("1.e4 e5 2.Nf3 2...Nc6 3. Bb5 ... a6 *"), 6);
+    }
+
+    #[test]
+    fn unmatched_closing_parenthesis() {
+        let err = ChessGame::from_pgn("1. e4 ) e5 2. Nf3 Nc6 *").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert_eq!(err.message, "Unmatched )");
+    }
+
+    #[test]
+    fn unclosed_variation_and_comment() {
+        let err = ChessGame::from_pgn("1. e4 e5\n2. Nf3 (2. Nc3 Nc6\n2... Nc6 *").unwrap_err();
+        assert_eq!((err.line, err.message.as_str()), (2, "Unclosed variation"));
+        let err = ChessGame::from_pgn("1. e4 {best by test\n e5 *").unwrap_err();
+        assert_eq!((err.line, err.message.as_str()), (1, "Unclosed comment"));
+    }
+
+    #[test]
+    fn skips_comments_variations_and_nags() {
+        let pgn = "[Event \"Test\"]\n[White \"A\"]\n\n\
+            1. e4 {king's pawn} e5 $1 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) Nc6 ; rest of line\n\
+            3. Bb5 a6 1/2-1/2";
+        assert_eq!(plies(pgn), 6);
+    }
+
+    #[test]
+    fn starts_from_the_fen_tag() {
+        let pgn = "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/4K2R w K - 0 1\"]\n\n1. O-O Kd7 *";
+        let game = ChessGame::from_pgn(pgn).unwrap();
+        assert_eq!(game.board.to_fen(), "8/3k4/8/8/8/8/8/5RK1 w - - 2 2");
+    }
+
+    #[test]
+    fn only_reads_the_first_game() {
+        let pgn = "[Event \"One\"]\n\n1. e4 e5 *\n\n[Event \"Two\"]\n\n1. d4 d5 2. c4 *";
+        assert_eq!(plies(pgn), 2);
+    }
+
+    #[test]
+    fn export_then_import() {
+        let pgn = "1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0";
+        let game = ChessGame::from_pgn(pgn).unwrap();
+        assert!(game.is_over);
+        let exported = game.to_pgn();
+        assert!(exported.contains("[Result \"1-0\"]"));
+        assert!(exported.ends_with("1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0\n"));
+        assert_eq!(ChessGame::from_pgn(&exported).unwrap().board, game.board);
+    }
+
+    #[test]
+    fn reports_where_moves_fail() {
+        let err = ChessGame::from_pgn("1. e4 e5\n2. Ke3 *").unwrap_err();
+        assert_eq!((err.line, err.ply), (2, Some(3)));
+        let err = ChessGame::from_pgn("1. f3 e5 2. g4 Qh4# 3. a3 *").unwrap_err();
+        assert_eq!(err.ply, Some(5));
+        let err = ChessGame::from_pgn("[FEN \"8/8/8 w - - 0 1\"]\n\n*").unwrap_err();
+        assert_eq!((err.line, err.ply), (1, None));
+        let err = ChessGame::from_pgn("[Event Test]\n1. e4 *").unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+}
@@ -0,0 +1,56 @@
+//! Named starting positions a lobby can be created from, for endgame drills and opening study.
+
+use crate::fen::STARTING_FEN;
+
+pub const SETUPS: &[(&str, &str)] = &[
+    ("standard", STARTING_FEN),
+    // Endgames
+    ("queen-vs-king", "4k3/8/8/8/8/8/8/3QK3 w - - 0 1"),
+    ("rook-vs-king", "4k3/8/8/8/8/8/8/R3K3 w - - 0 1"),
+    ("two-bishops-vs-king", "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1"),
+    (
+        "bishop-and-knight-vs-king",
+        "7k/8/8/8/8/8/8/1N2KB2 w - - 0 1",
+    ),
+    ("king-and-pawn-vs-king", "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"),
+    ("lucena", "1K1k4/1P6/8/8/8/8/r7/2R5 w - - 0 1"),
+    ("philidor", "4k3/8/8/4PK2/8/8/r7/6R1 b - - 0 1"),
+    // Openings
+    (
+        "italian-game",
+        "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    ),
+    (
+        "ruy-lopez",
+        "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3",
+    ),
+    (
+        "sicilian-defence",
+        "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+    ),
+    (
+        "french-defence",
+        "rnbqkbnr/pppp1ppp/4p3/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+    ),
+    (
+        "caro-kann-defence",
+        "rnbqkbnr/pp1ppppp/2p5/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+    ),
+    (
+        "queens-gambit",
+        "rnbqkbnr/ppp1pppp/8/3p4/2PP4/8/PP2PPPP/RNBQKBNR b KQkq c3 0 2",
+    ),
+    (
+        "kings-indian-defence",
+        "rnbqk2r/ppppppbp/5np1/8/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq - 2 4",
+    ),
+];
+
+/** FEN of the setup called `name`.
+ */
+pub fn find_setup(name: &str) -> Option<&'static str> {
+    SETUPS
+        .iter()
+        .find(|(setup, _)| *setup == name)
+        .map(|(_, fen)| *fen)
+}
@@ -0,0 +1,1030 @@
+use crate::clock::{Clock, Correspondence, TimeControl};
+use crate::data::{Board, BoardStatus, ChessBoard, Color, Move, PieceKind, Square};
+use crate::engine::Strength;
+use crate::fen::STARTING_FEN;
+use crate::setups::find_setup;
+use serde::{Deserialize, Serialize};
+
+/** Body of `/user/register` and `/user/login`.
+ */
+#[derive(Deserialize, Serialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+/** Answer to a successful registration or login. The token goes in the `Authorization` header
+ * as `Bearer <token>`, and identifies the player in every request that acts for them.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct Session {
+    pub username: String,
+    pub token: String,
+}
+
+/** Opened by the player taking the first seat, the logged-in one or else a guest.
+ */
+#[derive(Deserialize, Serialize, Default)]
+pub struct NewLobby {
+    /** Name shown for a guest, ignored when logged in.
+     */
+    #[serde(default)]
+    pub player: Option<String>,
+    /** Position to start from, takes precedence over `setup`.
+     */
+    #[serde(default)]
+    pub fen: Option<String>,
+    /** Name of one of the positions in [`crate::setups::SETUPS`].
+     */
+    #[serde(default)]
+    pub setup: Option<String>,
+    /** Untimed when not given.
+     */
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+    /** Makes a correspondence game, can't be combined with `time_control`.
+     */
+    #[serde(default)]
+    pub days_per_move: Option<u32>,
+    /** Side the creator plays, White when not given.
+     */
+    #[serde(default)]
+    pub color: ColorChoice,
+    /** Plays against the computer at this strength instead of waiting for a second player.
+     */
+    #[serde(default)]
+    pub computer: Option<Strength>,
+}
+
+impl NewLobby {
+    /** FEN the lobby starts from, the standard position when neither `fen` nor `setup` is given.
+     */
+    pub fn starting_fen(&self) -> Result<&str, String> {
+        match (&self.fen, &self.setup) {
+            (Some(fen), _) => Ok(fen),
+            (None, Some(setup)) => find_setup(setup).ok_or(format!("Unknown setup {setup}")),
+            (None, None) => Ok(STARTING_FEN),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ImportPgn {
+    #[serde(default)]
+    pub player: Option<String>,
+    pub pgn: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct JoinLobby {
+    pub id: String,
+    #[serde(default)]
+    pub player: Option<String>,
+}
+
+/** Body of the requests a seated player makes about the lobby as a whole: leaving, aborting,
+ * resigning, draw offers and claiming a win, sent with the player's seat or session token.
+ */
+#[derive(Deserialize, Serialize)]
+pub struct LobbyAction {
+    pub id: String,
+}
+
+/** Answer to creating, importing or joining a lobby. The token is only ever given to the
+ * seated player and goes in the `Authorization` header (`Bearer <token>`) of their moves.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct Seat {
+    pub token: String,
+    pub game: ChessGame,
+}
+
+/** The move is given either as `from`/`to` squares (`board[file][rank]` coordinates, see
+ * [`crate::data::Square`]) or as text in `move`, in SAN (`"Nf3"`) or UCI (`"g1f3"`).
+ */
+#[derive(Deserialize, Serialize)]
+pub struct MoveChessPiece {
+    pub id: String,
+    #[serde(default)]
+    pub from: Option<Square>,
+    #[serde(default)]
+    pub to: Option<Square>,
+    /** Required when a pawn reaches the last rank.
+     */
+    #[serde(default)]
+    pub promotion: Option<PieceKind>,
+    #[serde(default, rename = "move")]
+    pub notation: Option<String>,
+}
+
+impl MoveChessPiece {
+    /** The requested move, as long as it can be read. Whether it is legal is up to
+     * [`ChessGame::validify_move`].
+     */
+    pub fn get_move(&self, board: &ChessBoard) -> Result<Move, MoveRequestError> {
+        match (&self.notation, self.from, self.to) {
+            (Some(notation), _, _) => board
+                .parse_move(notation)
+                .map_err(MoveRequestError::Unreadable),
+            (None, Some(from), Some(to)) => {
+                if !on_board(from) || !on_board(to) {
+                    return Err(MoveRequestError::Malformed(
+                        "From and to must be squares of the board".to_string(),
+                    ));
+                }
+                Ok(Move {
+                    from,
+                    to,
+                    promotion: self.promotion,
+                })
+            }
+            _ => Err(MoveRequestError::Malformed(
+                "Either move or from and to are required".to_string(),
+            )),
+        }
+    }
+}
+
+/** Why [`MoveChessPiece::get_move`] gave no move.
+ */
+#[derive(PartialEq, Clone, Debug)]
+pub enum MoveRequestError {
+    /** The request doesn't name a move at all, or names squares off the board. */
+    Malformed(String),
+    /** The notation in `move` is no move on this board. */
+    Unreadable(String),
+}
+
+fn on_board(square: Square) -> bool {
+    square.0 < 8 && square.1 < 8
+}
+
+/** Pushed to the players and spectators of a lobby when something happens in it. Every event
+ * carries the whole game as it is after the change.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+#[serde(tag = "type")]
+pub enum LobbyEvent {
+    /** Sent once when subscribing, for each lobby when subscribing to all of them. */
+    Snapshot { game: ChessGame },
+    Created { game: ChessGame },
+    Join { player: String, game: ChessGame },
+    Move { san: String, game: ChessGame },
+    /** The player gave up their seat before the game started. */
+    Leave { player: String, game: ChessGame },
+    Abort { player: String, game: ChessGame },
+    Resign { player: String, game: ChessGame },
+    DrawOffer { player: String, game: ChessGame },
+    DrawDeclined { player: String, game: ChessGame },
+    GameOver {
+        result: GameResult,
+        reason: EndReason,
+        game: ChessGame,
+    },
+}
+
+impl LobbyEvent {
+    pub fn game(&self) -> &ChessGame {
+        match self {
+            LobbyEvent::Snapshot { game }
+            | LobbyEvent::Created { game }
+            | LobbyEvent::Join { game, .. }
+            | LobbyEvent::Move { game, .. }
+            | LobbyEvent::Leave { game, .. }
+            | LobbyEvent::Abort { game, .. }
+            | LobbyEvent::Resign { game, .. }
+            | LobbyEvent::DrawOffer { game, .. }
+            | LobbyEvent::DrawDeclined { game, .. }
+            | LobbyEvent::GameOver { game, .. } => game,
+        }
+    }
+}
+
+/** Body of every error response from the server.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct ApiErrorBody {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ErrorCode {
+    /** 400: the request body or the position/PGN it carries can't be read. */
+    InvalidRequest,
+    /** 404 */
+    LobbyNotFound,
+    /** 403: the token holds no seat in the lobby. */
+    NotAPlayer,
+    /** 409: both seats are taken. */
+    LobbyFull,
+    /** 409 */
+    NotYourTurn,
+    /** 409 */
+    GameOver,
+    /** 422 */
+    IllegalMove,
+    /** 401: the `Authorization` header is missing or its token is unknown, or the login failed. */
+    Unauthorized,
+    /** 409 */
+    UsernameTaken,
+    /** 409: not a correspondence game, or the opponent still has time to move. */
+    DeadlineNotPassed,
+    /** 409: joining a lobby the player already sits in. */
+    AlreadySeated,
+    /** 409: the lobby's state doesn't allow it, like aborting after the first move. */
+    InvalidState,
+    /** 500 */
+    Internal,
+}
+
+/** Side the creator of a lobby asked for. `Random` is drawn when the second player joins.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum ColorChoice {
+    #[default]
+    White,
+    Black,
+    Random,
+}
+
+/** Where a lobby is in its life. A lobby opens `Waiting` for a second player, is `InProgress`
+ * once both seats are taken, and ends `Finished` with a result or `Aborted` without one.
+ */
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum LobbyState {
+    #[default]
+    Waiting,
+    InProgress,
+    Finished,
+    Aborted,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug, Default)]
+pub struct ChessGame {
+    pub id: String,
+    pub board: ChessBoard,
+    pub color_player_1: Color,
+    pub player1: String,
+    pub player2: String,
+    pub is_started: bool,
+    pub is_over: bool,
+    /** Whether the side to move is in check.
+     */
+    pub in_check: bool,
+    pub result: Option<GameResult>,
+    pub end_reason: Option<EndReason>,
+    /** Position the game started from, needed to replay `board.moves`.
+     */
+    pub start_fen: String,
+    /** Unix timestamp (seconds) of the lobby creation, 0 if unknown.
+     */
+    pub created_at: u64,
+    /** Goes up every time the lobby changes, used as its ETag.
+     */
+    pub version: u64,
+    /** Remaining times of a timed game, `None` when untimed.
+     */
+    #[serde(default)]
+    pub clock: Option<Clock>,
+    /** Move deadline of a correspondence game.
+     */
+    #[serde(default)]
+    pub correspondence: Option<Correspondence>,
+    #[serde(default)]
+    pub state: LobbyState,
+    /** Moves on the board when the game started, a game can be aborted until one more is played.
+     */
+    #[serde(default)]
+    pub started_at_ply: usize,
+    /** Side whose draw offer is waiting for an answer. It lapses when the opponent moves.
+     */
+    #[serde(default)]
+    pub draw_offer: Option<Color>,
+    /** What the creator asked for, `color_player_1` is only settled once the game starts.
+     */
+    #[serde(default)]
+    pub color_choice: ColorChoice,
+    /** Strength of the computer sitting in the second seat, `None` when it is a person.
+     */
+    #[serde(default)]
+    pub computer: Option<Strength>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum EndReason {
+    Checkmate,
+    Stalemate,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    InsufficientMaterial,
+    /** A player ran out of time; a draw when the opponent has nothing left to mate with. */
+    Timeout,
+    /** The opponent claimed the win after a correspondence deadline passed. */
+    DeadlineMissed,
+    Resignation,
+    /** Both players agreed to a draw. */
+    Agreement,
+}
+
+impl ChessGame {
+    pub fn new(player1_color: Color) -> Self {
+        Self {
+            board: ChessBoard::new(gen_matrix()),
+            id: String::new(),
+            player1: String::new(),
+            player2: String::new(),
+            color_player_1: player1_color,
+            is_started: false,
+            is_over: false,
+            in_check: false,
+            result: None,
+            end_reason: None,
+            start_fen: STARTING_FEN.to_string(),
+            created_at: 0,
+            version: 0,
+            clock: None,
+            correspondence: None,
+            state: LobbyState::Waiting,
+            started_at_ply: 0,
+            draw_offer: None,
+            color_choice: ColorChoice::default(),
+            computer: None,
+        }
+    }
+
+    /** A lobby whose creator plays the chosen side, White until the draw for `Random`.
+     */
+    pub fn with_color_choice(choice: ColorChoice) -> Self {
+        let color = match choice {
+            ColorChoice::Black => Color::Black,
+            ColorChoice::White | ColorChoice::Random => Color::White,
+        };
+        Self {
+            color_choice: choice,
+            ..Self::new(color)
+        }
+    }
+
+    /** Color played from a seat, 0 for `player1` and 1 for `player2`.
+     */
+    pub fn seat_color(&self, seat: usize) -> Color {
+        if seat == 0 {
+            self.color_player_1
+        } else {
+            self.color_player_1.opposite()
+        }
+    }
+
+    /** Strength to search with when the game is on and the computer is to move.
+     */
+    pub fn computer_to_move(&self) -> Option<Strength> {
+        if self.state == LobbyState::InProgress && self.board.turn == self.seat_color(1) {
+            self.computer
+        } else {
+            None
+        }
+    }
+
+    /** Name of the player in a seat, empty while the seat is free.
+     */
+    pub fn seat_player(&self, seat: usize) -> &str {
+        if seat == 0 {
+            &self.player1
+        } else {
+            &self.player2
+        }
+    }
+
+    /**
+     * Checks that the move is legal for the side to move, including that it doesn't leave its own king in check
+     * and that a pawn reaching the last rank says what it promotes to.
+     */
+    pub fn validify_move(&mut self, mv: &Move) -> bool {
+        self.board.is_legal(mv)
+    }
+
+    /**
+     * Moves a piece from one square to another and passes the turn. Only verification is that the piece exists (is not none).
+     * Ends the game if the side now to move is checkmated or stalemated, or if the position is a draw.
+     */
+    pub fn move_piece(&mut self, mv: Move) {
+        if self
+            .draw_offer
+            .is_some_and(|color| color != self.board.turn)
+        {
+            self.draw_offer = None;
+        }
+        self.board.move_piece(mv);
+        self.update_status();
+        self.version += 1;
+    }
+
+    pub fn update_status(&mut self) {
+        let status = self.board.status();
+        self.in_check = matches!(status, BoardStatus::Check | BoardStatus::Checkmate);
+        match status {
+            BoardStatus::Checkmate => {
+                let result = match self.board.turn {
+                    Color::White => GameResult::BlackWins,
+                    Color::Black => GameResult::WhiteWins,
+                };
+                self.finish(result, EndReason::Checkmate);
+            }
+            BoardStatus::Stalemate => self.finish(GameResult::Draw, EndReason::Stalemate),
+            BoardStatus::Check | BoardStatus::Ongoing => {
+                if let Some(reason) = self.draw_reason() {
+                    self.finish(GameResult::Draw, reason);
+                }
+            }
+        }
+    }
+
+    fn draw_reason(&self) -> Option<EndReason> {
+        if self.board.is_insufficient_material() {
+            Some(EndReason::InsufficientMaterial)
+        } else if self.board.repetitions() >= 3 {
+            Some(EndReason::ThreefoldRepetition)
+        } else if self.board.halfmove_clock >= 100 {
+            Some(EndReason::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    pub fn finish(&mut self, result: GameResult, reason: EndReason) {
+        self.state = LobbyState::Finished;
+        self.draw_offer = None;
+        self.is_over = true;
+        self.board.game_over = true;
+        self.result = Some(result);
+        self.end_reason = Some(reason);
+        self.stop_clocks();
+    }
+
+    /** Starts the clock of the side to move, or its move deadline in a correspondence game,
+     * unless already started. `now` is a Unix timestamp in milliseconds, like every time given
+     * to the clock.
+     */
+    pub fn start_clock(&mut self, now: u64) {
+        if self.is_over {
+            return;
+        }
+        if let Some(clock) = &mut self.clock {
+            clock.start(now);
+        }
+        if let Some(correspondence) = &mut self.correspondence {
+            if correspondence.deadline.is_none() {
+                correspondence.restart(now);
+            }
+        }
+    }
+
+    /** Ends the turn of the side to move, to be called right before playing its move.
+     */
+    pub fn press_clock(&mut self, now: u64) {
+        let turn = self.board.turn;
+        if let Some(clock) = &mut self.clock {
+            clock.press(turn, now);
+        }
+        if let Some(correspondence) = &mut self.correspondence {
+            if correspondence.deadline.is_some() {
+                correspondence.restart(now);
+            }
+        }
+    }
+
+    /** Ends the game in favour of `claimant` if its opponent, to move, let the deadline of a
+     * correspondence game pass. Says whether the claim was accepted.
+     */
+    pub fn claim_deadline(&mut self, claimant: Color, now: u64) -> bool {
+        let missed = self
+            .correspondence
+            .is_some_and(|correspondence| correspondence.is_missed(now));
+        if self.is_over || !missed || self.board.turn == claimant {
+            return false;
+        }
+        let result = if !self.board.has_mating_material(claimant) {
+            GameResult::Draw
+        } else if claimant == Color::White {
+            GameResult::WhiteWins
+        } else {
+            GameResult::BlackWins
+        };
+        self.finish(result, EndReason::DeadlineMissed);
+        self.version += 1;
+        true
+    }
+
+    /** Takes the game from `Waiting` to `InProgress` once the second seat is taken, starting
+     * the clocks at `now`. A random colour goes to the creator as White when `coin` is true.
+     */
+    pub fn start(&mut self, now: u64, coin: bool) {
+        if self.state != LobbyState::Waiting {
+            return;
+        }
+        if self.color_choice == ColorChoice::Random {
+            self.color_player_1 = if coin { Color::White } else { Color::Black };
+        }
+        self.state = LobbyState::InProgress;
+        self.is_started = true;
+        self.started_at_ply = self.board.moves.len();
+        self.start_clock(now);
+    }
+
+    /** Frees `seat`: the creator leaving a waiting lobby closes it, the second player leaving
+     * before the first move reopens it for someone else.
+     */
+    pub fn leave(&mut self, seat: usize) -> Result<(), String> {
+        match (self.state, seat) {
+            (LobbyState::Waiting, 0) => {
+                self.close();
+            }
+            (LobbyState::InProgress, 1) if self.board.moves.len() == self.started_at_ply => {
+                self.player2.clear();
+                self.state = LobbyState::Waiting;
+                self.is_started = false;
+                self.draw_offer = None;
+                self.stop_clocks();
+            }
+            (LobbyState::InProgress, _) => {
+                return Err("The game has started, abort or resign instead".to_string())
+            }
+            _ => return Err("The lobby is closed".to_string()),
+        }
+        self.version += 1;
+        Ok(())
+    }
+
+    /** Ends the game without a result, as long as no move was played since it started.
+     */
+    pub fn abort(&mut self) -> Result<(), String> {
+        if self.state != LobbyState::InProgress {
+            return Err("Only a game in progress can be aborted".to_string());
+        }
+        if self.board.moves.len() != self.started_at_ply {
+            return Err("A move was played, resign instead".to_string());
+        }
+        self.close();
+        self.version += 1;
+        Ok(())
+    }
+
+    pub fn resign(&mut self, color: Color) -> Result<(), String> {
+        if self.state != LobbyState::InProgress {
+            return Err("Only a game in progress can be resigned".to_string());
+        }
+        let result = match color {
+            Color::White => GameResult::BlackWins,
+            Color::Black => GameResult::WhiteWins,
+        };
+        self.finish(result, EndReason::Resignation);
+        self.version += 1;
+        Ok(())
+    }
+
+    pub fn offer_draw(&mut self, color: Color) -> Result<(), String> {
+        if self.state != LobbyState::InProgress {
+            return Err("Draws can only be offered in a game in progress".to_string());
+        }
+        match self.draw_offer {
+            Some(offer) if offer == color => Err("You already offered a draw".to_string()),
+            Some(_) => Err("Your opponent already offered a draw, accept it".to_string()),
+            None => {
+                self.draw_offer = Some(color);
+                self.version += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /** Accepts the opponent's pending offer, ending the game in a draw.
+     */
+    pub fn accept_draw(&mut self, color: Color) -> Result<(), String> {
+        self.opponent_offer(color)?;
+        self.finish(GameResult::Draw, EndReason::Agreement);
+        self.version += 1;
+        Ok(())
+    }
+
+    pub fn decline_draw(&mut self, color: Color) -> Result<(), String> {
+        self.opponent_offer(color)?;
+        self.draw_offer = None;
+        self.version += 1;
+        Ok(())
+    }
+
+    fn opponent_offer(&self, color: Color) -> Result<(), String> {
+        match self.draw_offer {
+            Some(offer) if offer != color && self.state == LobbyState::InProgress => Ok(()),
+            _ => Err("Your opponent hasn't offered a draw".to_string()),
+        }
+    }
+
+    /** Stops the clocks without charging the side to move for its time so far.
+     */
+    fn stop_clocks(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            clock.running_since = None;
+        }
+        if let Some(correspondence) = &mut self.correspondence {
+            correspondence.deadline = None;
+        }
+    }
+
+    fn close(&mut self) {
+        self.state = LobbyState::Aborted;
+        self.is_over = true;
+        self.board.game_over = true;
+        self.draw_offer = None;
+        self.stop_clocks();
+    }
+
+    /** Ends the game if the side to move has run out of time at `now`, and says whether it did.
+     */
+    pub fn flag_fall(&mut self, now: u64) -> bool {
+        let turn = self.board.turn;
+        let flagged = match &self.clock {
+            Some(clock) => {
+                !self.is_over
+                    && clock.running_since.is_some()
+                    && clock.remaining(turn, turn, now) == 0
+            }
+            None => false,
+        };
+        if !flagged {
+            return false;
+        }
+        if let Some(clock) = &mut self.clock {
+            clock.stop(turn, now);
+        }
+        let result = if !self.board.has_mating_material(turn.opposite()) {
+            GameResult::Draw
+        } else if turn == Color::White {
+            GameResult::BlackWins
+        } else {
+            GameResult::WhiteWins
+        };
+        self.finish(result, EndReason::Timeout);
+        self.version += 1;
+        true
+    }
+
+    pub fn load_new(&mut self) {
+        self.load_fen(STARTING_FEN)
+            .expect("the starting position is valid FEN");
+    }
+
+    /**
+     * Replaces the board with the position described by `fen`, ending the game right away if nobody can move.
+     */
+    pub fn load_fen(&mut self, fen: &str) -> Result<(), String> {
+        self.board = ChessBoard::from_fen(fen)?;
+        self.start_fen = self.board.to_fen();
+        self.update_status();
+        Ok(())
+    }
+}
+
+fn gen_matrix() -> Board {
+    let mut matrix: Board = vec![];
+    for _ in 0..8 {
+        let mut row: Vec<Option<(PieceKind, Color)>> = vec![];
+        row.resize(8, None);
+        matrix.push(row);
+    }
+    matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{IncrementKind, DAY_MS};
+
+    fn move_request(
+        from: Option<Square>,
+        to: Option<Square>,
+        notation: Option<&str>,
+    ) -> MoveChessPiece {
+        MoveChessPiece {
+            id: String::new(),
+            from,
+            to,
+            promotion: None,
+            notation: notation.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn reads_moves_from_squares_or_notation() {
+        let board = ChessBoard::from_fen(STARTING_FEN).unwrap();
+        let e4 = Move::new((4, 1), (4, 3));
+        assert_eq!(
+            move_request(Some((4, 1)), Some((4, 3)), None).get_move(&board),
+            Ok(e4)
+        );
+        assert_eq!(
+            move_request(None, None, Some("e4")).get_move(&board),
+            Ok(e4)
+        );
+        assert_eq!(
+            move_request(None, None, Some("e2e4")).get_move(&board),
+            Ok(e4)
+        );
+    }
+
+    #[test]
+    fn tells_malformed_requests_from_unreadable_moves() {
+        let board = ChessBoard::from_fen(STARTING_FEN).unwrap();
+        for request in [
+            move_request(None, None, None),
+            move_request(Some((4, 1)), None, None),
+            move_request(Some((200, 1)), Some((4, 3)), None),
+            move_request(Some((4, 1)), Some((4, 8)), None),
+        ] {
+            assert!(matches!(
+                request.get_move(&board),
+                Err(MoveRequestError::Malformed(_))
+            ));
+        }
+        assert!(matches!(
+            move_request(None, None, Some("Ke9")).get_move(&board),
+            Err(MoveRequestError::Unreadable(_))
+        ));
+    }
+
+    /** A game between two seated players, White to move and its clock running since 0. */
+    fn timed_game(base_ms: u64, increment_ms: u64, kind: IncrementKind) -> ChessGame {
+        let mut game = ChessGame::with_color_choice(ColorChoice::White);
+        game.load_new();
+        game.clock = Some(Clock::new(TimeControl {
+            base_ms,
+            increment_ms,
+            kind,
+        }));
+        game.start(0, false);
+        game
+    }
+
+    #[test]
+    fn press_clock_adds_the_fischer_increment() {
+        let mut game = timed_game(60_000, 2_000, IncrementKind::Fischer);
+        game.press_clock(5_000);
+        game.move_piece(Move::new((4, 1), (4, 3)));
+        game.press_clock(6_000);
+        game.move_piece(Move::new((4, 6), (4, 4)));
+        let clock = game.clock.unwrap();
+        assert_eq!(clock.white_ms, 57_000);
+        assert_eq!(clock.black_ms, 61_000);
+        assert_eq!(clock.running_since, Some(6_000));
+    }
+
+    #[test]
+    fn press_clock_gives_back_at_most_the_bronstein_delay() {
+        let mut game = timed_game(60_000, 3_000, IncrementKind::Bronstein);
+        game.press_clock(1_000);
+        game.move_piece(Move::new((4, 1), (4, 3)));
+        game.press_clock(11_000);
+        game.move_piece(Move::new((4, 6), (4, 4)));
+        let clock = game.clock.unwrap();
+        assert_eq!(clock.white_ms, 60_000);
+        assert_eq!(clock.black_ms, 53_000);
+    }
+
+    #[test]
+    fn flag_falls_once_the_side_to_move_is_out_of_time() {
+        let mut game = timed_game(60_000, 0, IncrementKind::Fischer);
+        assert!(!game.flag_fall(59_999));
+        assert!(!game.is_over);
+
+        assert!(game.flag_fall(60_000));
+        assert_eq!(game.state, LobbyState::Finished);
+        assert_eq!(game.result, Some(GameResult::BlackWins));
+        assert_eq!(game.end_reason, Some(EndReason::Timeout));
+        let clock = game.clock.unwrap();
+        assert_eq!(clock.white_ms, 0);
+        assert_eq!(clock.running_since, None);
+        assert!(!game.flag_fall(70_000));
+    }
+
+    #[test]
+    fn flag_fall_is_a_draw_when_the_opponent_cannot_mate() {
+        let mut game = ChessGame::with_color_choice(ColorChoice::White);
+        game.load_fen("k7/8/8/8/8/8/8/KQ6 w - - 0 1").unwrap();
+        game.clock = Some(Clock::new(TimeControl {
+            base_ms: 1_000,
+            increment_ms: 0,
+            kind: IncrementKind::Fischer,
+        }));
+        game.start(0, false);
+        assert!(game.flag_fall(1_000));
+        assert_eq!(game.result, Some(GameResult::Draw));
+    }
+
+    #[test]
+    fn flag_fall_needs_a_running_clock() {
+        let mut untimed = ChessGame::with_color_choice(ColorChoice::White);
+        untimed.load_new();
+        untimed.start(0, false);
+        assert!(!untimed.flag_fall(u64::MAX));
+
+        let mut waiting = ChessGame::with_color_choice(ColorChoice::White);
+        waiting.load_new();
+        waiting.clock = Some(Clock::new(TimeControl {
+            base_ms: 1_000,
+            increment_ms: 0,
+            kind: IncrementKind::Fischer,
+        }));
+        assert!(!waiting.flag_fall(u64::MAX));
+    }
+
+    /** A correspondence game with a day per move, White to move and its deadline at `DAY_MS`. */
+    fn correspondence_game(fen: &str) -> ChessGame {
+        let mut game = ChessGame::with_color_choice(ColorChoice::White);
+        game.load_fen(fen).unwrap();
+        game.correspondence = Some(Correspondence::new(1).unwrap());
+        game.start(0, false);
+        game
+    }
+
+    #[test]
+    fn claim_deadline_needs_the_opponent_to_miss_its_deadline() {
+        let mut game = correspondence_game(STARTING_FEN);
+        assert!(!game.claim_deadline(Color::Black, DAY_MS));
+        assert!(!game.claim_deadline(Color::White, DAY_MS + 1));
+        assert!(!game.is_over);
+
+        assert!(game.claim_deadline(Color::Black, DAY_MS + 1));
+        assert_eq!(game.state, LobbyState::Finished);
+        assert_eq!(game.result, Some(GameResult::BlackWins));
+        assert_eq!(game.end_reason, Some(EndReason::DeadlineMissed));
+        assert_eq!(game.correspondence.unwrap().deadline, None);
+        assert!(!game.claim_deadline(Color::Black, 2 * DAY_MS));
+    }
+
+    #[test]
+    fn moving_restarts_the_deadline() {
+        let mut game = correspondence_game(STARTING_FEN);
+        game.press_clock(DAY_MS / 2);
+        game.move_piece(Move::new((4, 1), (4, 3)));
+        assert_eq!(
+            game.correspondence.unwrap().deadline,
+            Some(DAY_MS / 2 + DAY_MS)
+        );
+        assert!(!game.claim_deadline(Color::White, DAY_MS + 1));
+        assert!(game.claim_deadline(Color::White, 2 * DAY_MS));
+        assert_eq!(game.result, Some(GameResult::WhiteWins));
+    }
+
+    #[test]
+    fn claim_deadline_is_a_draw_without_mating_material() {
+        let mut game = correspondence_game("k7/8/8/8/8/8/8/KQ6 w - - 0 1");
+        assert!(game.claim_deadline(Color::Black, DAY_MS + 1));
+        assert_eq!(game.result, Some(GameResult::Draw));
+    }
+
+    #[test]
+    fn claim_deadline_needs_a_correspondence_game() {
+        let mut game = timed_game(1_000, 0, IncrementKind::Fischer);
+        assert!(!game.claim_deadline(Color::Black, u64::MAX));
+    }
+
+    fn waiting_lobby() -> ChessGame {
+        let mut game = ChessGame::with_color_choice(ColorChoice::White);
+        game.load_new();
+        game
+    }
+
+    fn started_game() -> ChessGame {
+        let mut game = waiting_lobby();
+        game.start(0, false);
+        game
+    }
+
+    #[test]
+    fn start_takes_a_waiting_lobby_in_progress_once() {
+        let mut game = waiting_lobby();
+        assert_eq!(game.state, LobbyState::Waiting);
+        game.start(0, false);
+        assert_eq!(game.state, LobbyState::InProgress);
+        assert!(game.is_started);
+        assert_eq!(game.started_at_ply, 0);
+
+        game.resign(Color::White).unwrap();
+        game.start(0, false);
+        assert_eq!(game.state, LobbyState::Finished);
+    }
+
+    #[test]
+    fn start_draws_the_colours_of_a_random_lobby() {
+        for (coin, color) in [(true, Color::White), (false, Color::Black)] {
+            let mut game = ChessGame::with_color_choice(ColorChoice::Random);
+            game.start(0, coin);
+            assert_eq!(game.color_player_1, color);
+        }
+    }
+
+    #[test]
+    fn leaving_closes_or_reopens_the_lobby() {
+        let mut game = waiting_lobby();
+        assert!(game.leave(1).is_err());
+        game.leave(0).unwrap();
+        assert_eq!(game.state, LobbyState::Aborted);
+        assert!(game.is_over);
+        assert!(game.leave(0).is_err());
+
+        let mut game = started_game();
+        game.player2 = "bob".to_string();
+        assert!(game.leave(0).is_err());
+        game.leave(1).unwrap();
+        assert_eq!(game.state, LobbyState::Waiting);
+        assert!(game.player2.is_empty());
+
+        game.start(0, false);
+        game.move_piece(Move::new((4, 1), (4, 3)));
+        assert!(game.leave(1).is_err());
+    }
+
+    #[test]
+    fn abort_only_before_the_first_move() {
+        assert!(waiting_lobby().abort().is_err());
+
+        let mut game = started_game();
+        let version = game.version;
+        game.abort().unwrap();
+        assert_eq!(game.state, LobbyState::Aborted);
+        assert_eq!(game.result, None);
+        assert_eq!(game.version, version + 1);
+        assert!(game.abort().is_err());
+
+        let mut game = started_game();
+        game.move_piece(Move::new((4, 1), (4, 3)));
+        assert!(game.abort().is_err());
+        assert_eq!(game.state, LobbyState::InProgress);
+    }
+
+    #[test]
+    fn abort_counts_moves_from_the_start_of_the_game() {
+        // An imported game starts after the moves it came with
+        let mut game = waiting_lobby();
+        game.move_piece(Move::new((4, 1), (4, 3)));
+        game.start(0, false);
+        assert_eq!(game.started_at_ply, 1);
+        game.abort().unwrap();
+    }
+
+    #[test]
+    fn resigning_gives_the_game_to_the_opponent() {
+        assert!(waiting_lobby().resign(Color::White).is_err());
+
+        let mut game = started_game();
+        game.resign(Color::Black).unwrap();
+        assert_eq!(game.state, LobbyState::Finished);
+        assert_eq!(game.result, Some(GameResult::WhiteWins));
+        assert_eq!(game.end_reason, Some(EndReason::Resignation));
+        assert!(game.resign(Color::White).is_err());
+    }
+
+    #[test]
+    fn a_draw_offer_is_accepted_or_declined_by_the_opponent() {
+        assert!(waiting_lobby().offer_draw(Color::White).is_err());
+
+        let mut game = started_game();
+        assert!(game.accept_draw(Color::Black).is_err());
+        game.offer_draw(Color::White).unwrap();
+        assert!(game.offer_draw(Color::White).is_err());
+        assert!(game.offer_draw(Color::Black).is_err());
+        assert!(game.accept_draw(Color::White).is_err());
+        assert!(game.decline_draw(Color::White).is_err());
+
+        game.decline_draw(Color::Black).unwrap();
+        assert_eq!(game.draw_offer, None);
+        assert!(game.accept_draw(Color::Black).is_err());
+
+        game.offer_draw(Color::Black).unwrap();
+        game.accept_draw(Color::White).unwrap();
+        assert_eq!(game.state, LobbyState::Finished);
+        assert_eq!(game.result, Some(GameResult::Draw));
+        assert_eq!(game.end_reason, Some(EndReason::Agreement));
+        assert_eq!(game.draw_offer, None);
+    }
+
+    #[test]
+    fn moving_instead_of_answering_declines_the_draw_offer() {
+        let mut game = started_game();
+        game.offer_draw(Color::White).unwrap();
+        game.move_piece(Move::new((4, 1), (4, 3)));
+        assert_eq!(game.draw_offer, Some(Color::White));
+
+        game.move_piece(Move::new((4, 6), (4, 4)));
+        assert_eq!(game.draw_offer, None);
+        assert!(game.accept_draw(Color::Black).is_err());
+    }
+}
@@ -1,13 +1,18 @@
-use data::{Board, ChessGame};
+use chess_core::clock::Clock;
+use chess_core::data::{ChessBoard, Color, Move, PieceKind, PROMOTION_PIECES};
+use chess_core::fen::STARTING_FEN;
+use chess_core::notation::san_moves;
+use chess_core::specs::{ChessGame, Credentials, JoinLobby, LobbyEvent, NewLobby, Seat, Session};
 use dioxus::prelude::*;
 use dioxus_router::prelude::*;
+use futures::StreamExt;
+use gloo_net::eventsource::futures::EventSource;
+use gloo_net::websocket::{futures::WebSocket, Message};
 use log::info;
+use std::collections::HashMap;
 
 //use futures::future::join_all;
 
-pub mod data;
-use crate::data::{ChessBoard, Color, PieceKind};
-
 #[derive(Debug, PartialEq, Clone)]
 pub struct StructExample {
     pub id: u32,
@@ -17,17 +22,20 @@ pub struct StructExample {
 
 #[inline_props]
 #[allow(non_snake_case)]
-pub(crate) fn ChessBoardComponent(cx: Scope<()>, board: UseRef<ChessBoard>) -> Element {
+pub(crate) fn ChessBoardComponent(cx: Scope<()>, board: UseRef<ChessBoard>, bottom: Color) -> Element {
+    // Pawn move to the last rank waiting for the player to pick a piece
+    let promotion: &UseRef<Option<Move>> = use_ref(cx, || None);
     cx.render(rsx! {
         div { class: "board",
-            (0..8).map(|i| {
+            board_order(*bottom == Color::Black).map(|i| {
                 rsx! {
                     div {
-                        (0..8).map(|j| {
+                        board_order(*bottom == Color::White).map(|j| {
                             rsx! {
                                 Square {
                                     position: (i,j),
                                     board: board.clone(),
+                                    promotion: promotion.clone(),
                                 }
                             }
                         })
@@ -35,33 +43,61 @@ pub(crate) fn ChessBoardComponent(cx: Scope<()>, board: UseRef<ChessBoard>) -> E
                 }
             })
         }
+        PromotionPicker { board: board.clone(), promotion: promotion.clone() }
     })
 }
 
-fn populate_board<'a>(board: &'a mut Board) {
-    board[0][0] = Some((PieceKind::Rook, Color::White));
-    board[1][0] = Some((PieceKind::Knight, Color::White));
-    board[2][0] = Some((PieceKind::Bishop, Color::White));
-    board[3][0] = Some((PieceKind::Queen, Color::White));
-    board[4][0] = Some((PieceKind::King, Color::White));
-    board[5][0] = Some((PieceKind::Bishop, Color::White));
-    board[6][0] = Some((PieceKind::Knight, Color::White));
-    board[7][0] = Some((PieceKind::Rook, Color::White));
-    for n in 0..8 {
-        board[n][1] = Some((PieceKind::Pawn, Color::White));
+/** Files or ranks in display order, `reversed` for the side at the bottom to read them from
+ * its own point of view.
+ */
+fn board_order(reversed: bool) -> Box<dyn Iterator<Item = u8>> {
+    if reversed {
+        Box::new((0..8).rev())
+    } else {
+        Box::new(0..8)
     }
+}
 
-    board[0][7] = Some((PieceKind::Rook, Color::Black));
-    board[1][7] = Some((PieceKind::Knight, Color::Black));
-    board[2][7] = Some((PieceKind::Bishop, Color::Black));
-    board[3][7] = Some((PieceKind::Queen, Color::Black));
-    board[4][7] = Some((PieceKind::King, Color::Black));
-    board[5][7] = Some((PieceKind::Bishop, Color::Black));
-    board[6][7] = Some((PieceKind::Knight, Color::Black));
-    board[7][7] = Some((PieceKind::Rook, Color::Black));
-    for n in 0..8 {
-        board[n][6] = Some((PieceKind::Pawn, Color::Black));
-    }
+#[inline_props]
+#[allow(non_snake_case)]
+fn PromotionPicker(
+    cx: Scope<()>,
+    board: UseRef<ChessBoard>,
+    promotion: UseRef<Option<Move>>,
+) -> Element {
+    let pending = (*promotion.read())?;
+    let color = board.read().turn;
+    cx.render(rsx! {
+        div { class: "promotion",
+            PROMOTION_PIECES.into_iter().map(|kind| {
+                let src = piece_image(kind, color);
+                rsx! {
+                    img {
+                        src: "{src}",
+                        class: "piece",
+                        onclick: move |_| {
+                            board.write().move_piece(Move { promotion: Some(kind), ..pending });
+                            promotion.set(None);
+                        },
+                    }
+                }
+            })
+        }
+    })
+}
+
+#[inline_props]
+#[allow(non_snake_case)]
+fn MoveList(cx: Scope<()>, board: UseRef<ChessBoard>) -> Element {
+    let moves = san_moves(STARTING_FEN, &board.read().moves);
+    cx.render(rsx! {
+        ol { class: "move-list",
+            moves.chunks(2).map(|pair| {
+                let line = pair.join(" ");
+                rsx! { li { "{line}" } }
+            })
+        }
+    })
 }
 
 fn activate_selected_color(i: u8, j: u8, selected: bool) -> String {
@@ -82,32 +118,32 @@ fn Piece(cx: Scope<()>, board: UseRef<ChessBoard>, position: (u8, u8)) -> Elemen
     if board.read().board[position.0 as usize][position.1 as usize].is_none() {
         return None;
     }
-    let binding = board.read();
-    let (kind, color) = binding.board[position.0 as usize][position.1 as usize]
-        .as_ref()
-        .unwrap();
-    let piece = (match kind {
+    let (kind, color) = board.read().board[position.0 as usize][position.1 as usize].unwrap();
+    let src = piece_image(kind, color);
+    cx.render(rsx! { img { src: "{src}", class: "piece" } })
+}
+
+fn piece_image(kind: PieceKind, color: Color) -> String {
+    let piece = match kind {
         PieceKind::Pawn => "pawn_",
         PieceKind::Knight => "knight_",
         PieceKind::Bishop => "bishop_",
         PieceKind::Rook => "rook_",
         PieceKind::Queen => "queen_",
         PieceKind::King => "king_",
-    })
-    .to_string()
-        + match color {
-            Color::White => "white",
-            Color::Black => "black",
-        };
-
-    let src = use_state(cx, || format!("{}.png", piece));
-    cx.render(rsx! { img { src: "{src}", class: "piece" } })
+    };
+    let color = match color {
+        Color::White => "white",
+        Color::Black => "black",
+    };
+    format!("{piece}{color}.png")
 }
 
 #[derive(PartialEq, Props)]
 pub struct SquareProps {
     position: (u8, u8),
     board: UseRef<ChessBoard>,
+    promotion: UseRef<Option<Move>>,
 }
 
 #[allow(non_snake_case)]
@@ -121,7 +157,22 @@ pub fn Square(cx: Scope<SquareProps>) -> Element {
             class: "{class}",
             onclick: move |_| {
                 info!("Clicked on square {i},{j}");
-                cx.props.board.write().select((i, j));
+                let board = &cx.props.board;
+                let selected = board.read().selected;
+                match selected {
+                    Some(from) if board.read().is_promotion(from, (i, j)) => {
+                        let mv = Move { promotion: Some(PieceKind::Queen), ..Move::new(from, (i, j)) };
+                        if board.read().is_legal(&mv) {
+                            cx.props.promotion.set(Some(Move::new(from, (i, j))));
+                        }
+                        board.write().selected = None;
+                    }
+                    Some(from) if board.read().is_legal(&Move::new(from, (i, j))) => {
+                        board.write().move_piece(Move::new(from, (i, j)));
+                        board.write().selected = None;
+                    }
+                    _ => board.write().select((i, j)),
+                }
             },
             Piece { board: cx.props.board.clone(), position: (i, j) }
         }
@@ -130,17 +181,8 @@ pub fn Square(cx: Scope<SquareProps>) -> Element {
 
 #[allow(non_snake_case)]
 pub fn ChessApp<'a>(cx: Scope<'a, ()>) -> Element {
-    let mut matrix: Board = vec![];
-    for _ in 0..8 {
-        let mut row: Vec<Option<(PieceKind, Color)>> = vec![];
-        row.resize(8, None);
-        matrix.push(row);
-    }
-
     let board: &UseRef<ChessBoard> = use_ref(cx, || {
-        let mut board = ChessBoard::new(matrix);
-        populate_board(&mut board.board);
-        board
+        ChessBoard::from_fen(STARTING_FEN).expect("the starting position is valid FEN")
     });
     info!("Board: {:?}", board.read().selected);
     cx.render(rsx! {
@@ -148,7 +190,8 @@ pub fn ChessApp<'a>(cx: Scope<'a, ()>) -> Element {
             style { include_str!("../src/style.css") }
             div {
                 h1 { class: "centered", "My application" }
-                ChessBoardComponent { board: board.clone() }
+                ChessBoardComponent { board: board.clone(), bottom: Color::White }
+                MoveList { board: board.clone() }
             }
         }
         footer { class: "info", p { "A footer" } }
@@ -156,6 +199,7 @@ pub fn ChessApp<'a>(cx: Scope<'a, ()>) -> Element {
 }
 
 pub static BASE_API_URL: &str = "http://localhost:8090";
+pub static BASE_WS_URL: &str = "ws://localhost:8090";
 pub static LOBBIES_API: &str = "/lobbies";
 pub static USER_API: &str = "/user";
 
@@ -167,6 +211,220 @@ pub async fn get_lobbies() -> Result<Vec<ChessGame>, reqwest::Error> {
     Ok(lobbies)
 }
 
+/** Creates an account and logs into it.
+ */
+pub async fn register(credentials: &Credentials) -> Result<Session, reqwest::Error> {
+    open_session("register", credentials).await
+}
+
+pub async fn login(credentials: &Credentials) -> Result<Session, reqwest::Error> {
+    open_session("login", credentials).await
+}
+
+async fn open_session(action: &str, credentials: &Credentials) -> Result<Session, reqwest::Error> {
+    let url = format!("{}{}/{}", BASE_API_URL, USER_API, action);
+    let response = reqwest::Client::new().post(&url).json(credentials).send().await?;
+    response.error_for_status()?.json::<Session>().await
+}
+
+/** Games of the logged-in player waiting for their move.
+ */
+pub async fn get_your_turn(session: &Session) -> Result<Vec<ChessGame>, reqwest::Error> {
+    let url = format!("{}{}/your-turn", BASE_API_URL, USER_API);
+    let response = reqwest::Client::new()
+        .get(&url)
+        .bearer_auth(&session.token)
+        .send()
+        .await?;
+    response.error_for_status()?.json::<Vec<ChessGame>>().await
+}
+
+/** Sends `body` to `path` with a bearer token: a session token to act as the logged-in
+ * player, or the seat token received when creating or joining a lobby to move in it.
+ */
+pub async fn post_as<B: serde::Serialize, T: serde::de::DeserializeOwned>(
+    token: &str,
+    path: &str,
+    body: &B,
+) -> Result<T, reqwest::Error> {
+    let url = format!("{}{}", BASE_API_URL, path);
+    let response = reqwest::Client::new()
+        .post(&url)
+        .bearer_auth(token)
+        .json(body)
+        .send()
+        .await?;
+    response.error_for_status()?.json::<T>().await
+}
+
+/** Seats this client took, by lobby id: the token to play with and which seat it is.
+ */
+#[derive(Default)]
+pub struct HeldSeats(HashMap<String, HeldSeat>);
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct HeldSeat {
+    pub token: String,
+    /** 0 for the creator, 1 for the player who joined. */
+    pub seat: usize,
+}
+
+/** Opens a lobby as a guest, the answer holds the creator's seat token.
+ */
+pub async fn create_lobby(body: &NewLobby) -> Result<Seat, reqwest::Error> {
+    let url = format!("{}/new-lobby", BASE_API_URL);
+    let response = reqwest::Client::new().post(&url).json(body).send().await?;
+    response.error_for_status()?.json::<Seat>().await
+}
+
+/** Takes the second seat of a lobby as a guest.
+ */
+pub async fn join_lobby(body: &JoinLobby) -> Result<Seat, reqwest::Error> {
+    let url = format!("{}/join-lobby", BASE_API_URL);
+    let response = reqwest::Client::new().post(&url).json(body).send().await?;
+    response.error_for_status()?.json::<Seat>().await
+}
+
+/** Fetches a lobby, `None` when it hasn't changed since `version` (the server answered 304).
+ */
+pub async fn get_lobby(id: &str, version: Option<u64>) -> Result<Option<ChessGame>, reqwest::Error> {
+    let url = format!("{}{}/{}", BASE_API_URL, LOBBIES_API, id);
+    let mut request = reqwest::Client::new().get(&url);
+    if let Some(version) = version {
+        request = request.header(reqwest::header::IF_NONE_MATCH, format!("\"{version}\""));
+    }
+    let response = request.send().await?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let lobby = response.error_for_status()?.json::<ChessGame>().await?;
+    Ok(Some(lobby))
+}
+
+/** Listens to the events of a lobby and keeps `board` and `game` in sync with the game they
+ * carry, until the server closes the connection.
+ */
+pub async fn subscribe_lobby(id: String, board: UseRef<ChessBoard>, game: UseRef<Option<ChessGame>>) {
+    let url = format!("{}{}/{}/ws", BASE_WS_URL, LOBBIES_API, id);
+    let mut socket = match WebSocket::open(&url) {
+        Ok(socket) => socket,
+        Err(err) => {
+            info!("Could not connect to {url}: {err}");
+            return;
+        }
+    };
+    while let Some(message) = socket.next().await {
+        match message {
+            Ok(Message::Text(text)) => match serde_json::from_str::<LobbyEvent>(&text) {
+                // Events published while the snapshot was taken can arrive after it
+                Ok(event) if game.read().as_ref().is_some_and(|current| event.game().version < current.version) => {}
+                Ok(event) => {
+                    info!("Lobby event: {:?}", event.game().version);
+                    board.set(event.game().board.clone());
+                    game.set(Some(event.game().clone()));
+                }
+                Err(err) => info!("Unreadable lobby event: {err}"),
+            },
+            Ok(Message::Bytes(_)) => {}
+            Err(err) => {
+                info!("Lobby {id} connection closed: {err}");
+                break;
+            }
+        }
+    }
+}
+
+/** Keeps `lobbies` up to date with the server-sent events of every lobby, until the stream ends.
+ */
+pub async fn watch_lobbies(lobbies: UseRef<Vec<ChessGame>>) {
+    let url = format!("{}{}/stream", BASE_API_URL, LOBBIES_API);
+    let mut source = match EventSource::new(&url) {
+        Ok(source) => source,
+        Err(err) => {
+            info!("Could not connect to {url}: {err}");
+            return;
+        }
+    };
+    let mut messages = match source.subscribe("message") {
+        Ok(messages) => messages,
+        Err(err) => {
+            info!("Could not listen to {url}: {err}");
+            return;
+        }
+    };
+    while let Some(Ok((_, message))) = messages.next().await {
+        let text = match message.data().as_string() {
+            Some(text) => text,
+            None => continue,
+        };
+        match serde_json::from_str::<LobbyEvent>(&text) {
+            Ok(event) => {
+                let game = event.game().clone();
+                lobbies.with_mut(|list| match list.iter_mut().find(|lobby| lobby.id == game.id) {
+                    Some(lobby) if game.version < lobby.version => {}
+                    Some(lobby) => *lobby = game,
+                    None => list.push(game),
+                });
+            }
+            Err(err) => info!("Unreadable lobby event: {err}"),
+        }
+    }
+    source.close();
+}
+
+#[inline_props]
+#[allow(non_snake_case)]
+pub fn LobbyGame(cx: Scope, id: String) -> Element {
+    let board: &UseRef<ChessBoard> = use_ref(cx, || {
+        ChessBoard::from_fen(STARTING_FEN).expect("the starting position is valid FEN")
+    });
+    let game: &UseRef<Option<ChessGame>> = use_ref(cx, || None);
+    use_future(cx, (id.clone(),), |(id,)| subscribe_lobby(id, board.clone(), game.clone()));
+    let clock = game.read().as_ref().and_then(|game| game.clock);
+    // Players see their own side at the bottom, spectators White's
+    let held = use_shared_state::<HeldSeats>(cx).and_then(|seats| seats.read().0.get(id).cloned());
+    let bottom = match (game.read().as_ref(), held) {
+        (Some(game), Some(held)) => game.seat_color(held.seat),
+        _ => Color::White,
+    };
+    cx.render(rsx! {
+        section { class: "whole",
+            style { include_str!("../src/style.css") }
+            div {
+                h1 { class: "centered", "Lobby {id}" }
+                ChessBoardComponent { board: board.clone(), bottom: bottom }
+                clock.map(|clock| rsx! { Clocks { clock: clock } })
+            }
+        }
+    })
+}
+
+/** Both players' remaining time as of the last move.
+ */
+#[inline_props]
+#[allow(non_snake_case)]
+fn Clocks(cx: Scope<()>, clock: Clock) -> Element {
+    let white = clock_text(clock.white_ms);
+    let black = clock_text(clock.black_ms);
+    cx.render(rsx! {
+        div { class: "clocks",
+            span { "White {white}" }
+            span { "Black {black}" }
+        }
+    })
+}
+
+/** `m:ss`, with tenths under ten seconds.
+ */
+fn clock_text(ms: u64) -> String {
+    let seconds = ms / 1000;
+    if seconds < 10 {
+        format!("0:0{}.{}", seconds, (ms % 1000) / 100)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
 #[derive(PartialEq, Props)]
 pub struct GameProps {
     game: ChessGame
@@ -174,13 +432,32 @@ pub struct GameProps {
 
 #[allow(non_snake_case)]
 pub fn Lobby (cx: Scope<GameProps>) -> Element {
+    let seats = use_shared_state::<HeldSeats>(cx).cloned();
+    let navigator = use_navigator(cx);
     cx.render(rsx! {
         div {
             class: "lobby-square",
             "{cx.props.game.id}"
             button {
+                onclick: move |_| {
+                    let id = cx.props.game.id.clone();
+                    let seats = seats.clone();
+                    let navigator = navigator.clone();
+                    cx.spawn(async move {
+                        match join_lobby(&JoinLobby { id: id.clone(), player: None }).await {
+                            Ok(seat) => {
+                                if let Some(seats) = seats {
+                                    seats.write().0.insert(id.clone(), HeldSeat { token: seat.token, seat: 1 });
+                                }
+                                navigator.push(Route::LobbyGame { id });
+                            }
+                            Err(err) => info!("Could not join lobby {id}: {err}"),
+                        }
+                    });
+                },
                 "Join"
             }
+            Link { to: Route::LobbyGame { id: cx.props.game.id.clone() }, "Watch" }
         }
 
     })
@@ -188,30 +465,18 @@ pub fn Lobby (cx: Scope<GameProps>) -> Element {
 
 #[allow(non_snake_case)]
 pub fn Lobbies<'a>(cx: Scope<'a, ()>) -> Element {
-    // Check coroutine https://dioxuslabs.com/learn/0.3/async/use_coroutine.html
-    let lobbies = use_future(cx, (), |_| get_lobbies());
-    // let lobbies2 = use_coroutine(cx, |_| {
-        //update lobbies here
-    // });
-    match lobbies.value() {
-        Some(Ok(list)) => {
-            // if it is, render the stories
-            render! {
-                div {
-                    // iterate over the stories with a for loop
-                    list.iter().map(|game| {
-                        render!( Lobby { game: game.clone() })
-                    })
-                }
-            }
-        }
-        Some(Err(err)) => {
-            // if there was an error, render the error
-            render! {"An error occurred while fetching stories {err}"}
-        }
-        None => {
-            // if the future is not resolved yet, render a loading message
-            render! {"Loading items"}
+    let lobbies: &UseRef<Vec<ChessGame>> = use_ref(cx, Vec::new);
+    // Filled by the snapshots sent when the stream opens, then updated as lobbies change
+    use_future(cx, (), |_| watch_lobbies(lobbies.clone()));
+    let list = lobbies.read();
+    if list.is_empty() {
+        return render! {"No lobbies yet"};
+    }
+    render! {
+        div {
+            list.iter().map(|game| {
+                render!( Lobby { game: game.clone() })
+            })
         }
     }
 }
@@ -219,6 +484,9 @@ pub fn Lobbies<'a>(cx: Scope<'a, ()>) -> Element {
 
 #[allow(non_snake_case)]
 pub fn LobbyApp<'a>(cx: Scope<'a, ()>) -> Element {
+    let name = use_state(cx, String::new);
+    let seats = use_shared_state::<HeldSeats>(cx).cloned();
+    let navigator = use_navigator(cx);
     cx.render(rsx! {
         head {
             style { include_str!("../src/style.css") }
@@ -231,9 +499,27 @@ pub fn LobbyApp<'a>(cx: Scope<'a, ()>) -> Element {
             h2 { "Create a lobby" }
             label { "Username" }
             input {
-
+                value: "{name}",
+                oninput: move |evt| name.set(evt.value.clone()),
             }
             button {
+                onclick: move |_| {
+                    let body = NewLobby { player: Some(name.get().clone()), ..NewLobby::default() };
+                    let seats = seats.clone();
+                    let navigator = navigator.clone();
+                    cx.spawn(async move {
+                        match create_lobby(&body).await {
+                            Ok(seat) => {
+                                let id = seat.game.id.clone();
+                                if let Some(seats) = seats {
+                                    seats.write().0.insert(id.clone(), HeldSeat { token: seat.token, seat: 0 });
+                                }
+                                navigator.push(Route::LobbyGame { id });
+                            }
+                            Err(err) => info!("Could not create a lobby: {err}"),
+                        }
+                    });
+                },
                 "Create"
             }
         }
@@ -242,6 +528,7 @@ pub fn LobbyApp<'a>(cx: Scope<'a, ()>) -> Element {
 
 #[allow(non_snake_case)]
 pub fn Render(cx: Scope) -> Element {
+    use_shared_state_provider(cx, HeldSeats::default);
     render! {
         Router::<Route> {}
     }
@@ -254,6 +541,8 @@ pub fn Render(cx: Scope) -> Element {
 enum Route {
     #[route("/game")]
     ChessApp {},
+    #[route("/lobby/:id")]
+    LobbyGame { id: String },
     #[route("/")]
     LobbyApp {},
 
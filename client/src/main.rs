@@ -4,8 +4,6 @@
 use chess::*;
 use log::LevelFilter;
 
-mod data;
-
 pub fn main() {
     dioxus_logger::init(LevelFilter::Info).expect("failed to init logger");
     dioxus_web::launch(Render);
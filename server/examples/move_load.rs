@@ -0,0 +1,174 @@
+//! Measures `/move` throughput against a running server: one thread per game, each creating a
+//! lobby as a guest, joining it as another and then playing random legal moves for both seats
+//! until time is up, starting a new game whenever one ends.
+//!
+//! ```bash
+//! cargo run --release -p hello
+//! cargo run --release -p hello --example move_load -- 127.0.0.1:8090 200 10
+//! ```
+//! The arguments are the server address, the number of concurrent games and the duration in
+//! seconds. The server saves every move to SQLite by default; start it with `CHESS_DB=memory` to
+//! leave the database out. On one core, 200 games played about 1,600 moves/s with SQLite and
+//! about 3,700 moves/s in memory.
+
+use chess_core::specs::{ChessGame, Seat};
+use serde::de::DeserializeOwned;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct Connection {
+    host: String,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    fn open(address: &str) -> Self {
+        let stream = TcpStream::connect(address).expect("the server is running");
+        stream.set_nodelay(true).unwrap();
+        Self {
+            host: address.to_string(),
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        }
+    }
+
+    /** Posts `body` over the kept-alive connection, as the player holding `token` if any, and
+     * reads the JSON response.
+     */
+    fn post<T: DeserializeOwned>(
+        &mut self,
+        path: &str,
+        token: Option<&str>,
+        body: &serde_json::Value,
+    ) -> T {
+        let body = body.to_string();
+        let authorization = token
+            .map(|token| format!("Authorization: Bearer {token}\r\n"))
+            .unwrap_or_default();
+        write!(
+            self.writer,
+            "POST {path} HTTP/1.1\r\nHost: {}\r\n{authorization}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            self.host,
+            body.len()
+        )
+        .unwrap();
+
+        let mut status = String::new();
+        self.reader.read_line(&mut status).unwrap();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut response = vec![0; length];
+        self.reader.read_exact(&mut response).unwrap();
+        if !status.contains(" 200 ") && !status.contains(" 201 ") {
+            panic!(
+                "{path} answered {}: {}",
+                status.trim_end(),
+                String::from_utf8_lossy(&response)
+            );
+        }
+        serde_json::from_slice(&response).expect("the response has the expected type")
+    }
+}
+
+/** Xorshift, enough to vary the games without another dependency.
+ */
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/** Plays games until `deadline` and returns how long each move took.
+ */
+fn play(address: String, index: usize, deadline: Instant) -> Vec<Duration> {
+    let mut connection = Connection::open(&address);
+    let mut random = 0x9E37_79B9_7F4A_7C15 ^ (index as u64 + 1);
+    let mut timings = vec![];
+
+    while Instant::now() < deadline {
+        let white: Seat = connection.post(
+            "/new-lobby",
+            None,
+            &serde_json::json!({ "player": format!("white-{index}") }),
+        );
+        let black: Seat = connection.post(
+            "/join-lobby",
+            None,
+            &serde_json::json!({ "id": white.game.id, "player": format!("black-{index}") }),
+        );
+        let mut game: ChessGame = black.game.clone();
+        while !game.is_over && Instant::now() < deadline {
+            let moves = game.board.get_all_moves();
+            let mv = &moves[next_random(&mut random) as usize % moves.len()];
+            let player = if game.board.turn == game.color_player_1 {
+                &white
+            } else {
+                &black
+            };
+            let body = serde_json::json!({ "id": game.id, "move": mv.to_uci() });
+            let start = Instant::now();
+            game = connection.post("/move", Some(&player.token), &body);
+            timings.push(start.elapsed());
+        }
+    }
+    timings
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let address = args.next().unwrap_or_else(|| "127.0.0.1:8090".to_string());
+    let games: usize = args
+        .next()
+        .map_or(100, |arg| arg.parse().expect("a number of games"));
+    let seconds: u64 = args
+        .next()
+        .map_or(10, |arg| arg.parse().expect("a number of seconds"));
+
+    let start = Instant::now();
+    let deadline = start + Duration::from_secs(seconds);
+    let players: Vec<_> = (0..games)
+        .map(|index| {
+            let address = address.clone();
+            thread::spawn(move || play(address, index, deadline))
+        })
+        .collect();
+    let mut timings: Vec<Duration> = players
+        .into_iter()
+        .flat_map(|player| player.join().unwrap())
+        .collect();
+    let elapsed = start.elapsed().as_secs_f64();
+
+    if timings.is_empty() {
+        println!("No move was played");
+        return;
+    }
+    timings.sort();
+    let percentile = |p: usize| timings[(timings.len() - 1) * p / 100];
+    println!(
+        "{} moves in {elapsed:.1}s over {games} games: {:.0} moves/s",
+        timings.len(),
+        timings.len() as f64 / elapsed
+    );
+    println!(
+        "latency p50 {:?}, p99 {:?}, max {:?}",
+        percentile(50),
+        percentile(99),
+        timings[timings.len() - 1]
+    );
+}
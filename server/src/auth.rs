@@ -0,0 +1,403 @@
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use actix_web::{post, web, FromRequest, HttpRequest, HttpResponse};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chess_core::specs::{Credentials, Session};
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::errors::ApiError;
+use crate::storage::{self, Account, AccountRepository, SeatHolder, SeatRepository, StorageError};
+
+const MAX_USERNAME_LENGTH: usize = 32;
+const MIN_PASSWORD_LENGTH: usize = 8;
+const GUEST_NAME: &str = "Anonymous";
+
+/** 256 random bits, hex encoded.
+ */
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/** A hash of no one's password, checked when logging in to an unknown username so that the
+ * answer takes as long as for a wrong password.
+ */
+fn dummy_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(new_token().as_bytes(), &salt)
+            .expect("the default parameters hash any password")
+            .to_string()
+    })
+}
+
+/** Session tokens of the logged-in players. They are only kept in memory, so players log in
+ * again after a restart.
+ */
+#[derive(Default)]
+pub(crate) struct Sessions {
+    tokens: Mutex<HashMap<String, String>>,
+}
+
+impl Sessions {
+    fn open(&self, username: &str) -> Session {
+        let token = new_token();
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.insert(token.clone(), username.to_string());
+        Session {
+            username: username.to_string(),
+            token,
+        }
+    }
+
+    fn username(&self, token: &str) -> Option<String> {
+        let tokens = self.tokens.lock().unwrap();
+        tokens.get(token).cloned()
+    }
+
+    fn close(&self, token: &str) {
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.remove(token);
+    }
+}
+
+/** Every seat taken in a lobby, by token and by account.
+ */
+pub(crate) struct Seats {
+    repository: Arc<dyn SeatRepository>,
+    holders: Mutex<HashMap<String, [Option<SeatHolder>; 2]>>,
+}
+
+impl Seats {
+    pub(crate) fn load(repository: Arc<dyn SeatRepository>) -> Result<Self, StorageError> {
+        let mut holders: HashMap<String, [Option<SeatHolder>; 2]> = HashMap::new();
+        for holder in repository.list_seats()? {
+            if holder.seat < 2 {
+                let seat = holder.seat;
+                let lobby_id = holder.lobby_id.clone();
+                holders.entry(lobby_id).or_default()[seat] = Some(holder);
+            }
+        }
+        Ok(Self {
+            repository,
+            holders: Mutex::new(holders),
+        })
+    }
+
+    /** Mints the token of `seat` (0 for `player1`, 1 for `player2`) and records who took it.
+     */
+    pub(crate) async fn take(
+        &self,
+        lobby_id: &str,
+        seat: usize,
+        account: Option<String>,
+    ) -> Result<String, StorageError> {
+        let holder = SeatHolder {
+            lobby_id: lobby_id.to_string(),
+            seat,
+            token: new_token(),
+            account,
+        };
+        let repository = self.repository.clone();
+        let holder =
+            storage::blocking(move || repository.save_seat(&holder).map(|()| holder)).await?;
+        let token = holder.token.clone();
+        let mut holders = self.holders.lock().unwrap();
+        holders.entry(lobby_id.to_string()).or_default()[seat] = Some(holder);
+        Ok(token)
+    }
+
+    /** Invalidates the token of `seat`, for a player leaving the lobby.
+     */
+    pub(crate) async fn release(&self, lobby_id: &str, seat: usize) -> Result<(), StorageError> {
+        let repository = self.repository.clone();
+        let id = lobby_id.to_string();
+        storage::blocking(move || repository.delete_seat(&id, seat)).await?;
+        let mut holders = self.holders.lock().unwrap();
+        if let Some(seats) = holders.get_mut(lobby_id) {
+            seats[seat] = None;
+        }
+        Ok(())
+    }
+
+    /** The seat `token` holds in the lobby: either its seat token, or the session token of the
+     * account that took it.
+     */
+    pub(crate) fn seat_of(
+        &self,
+        lobby_id: &str,
+        token: &str,
+        sessions: &Sessions,
+    ) -> Option<usize> {
+        let username = sessions.username(token);
+        let holders = self.holders.lock().unwrap();
+        holders
+            .get(lobby_id)?
+            .iter()
+            .position(|holder| match holder {
+                Some(holder) => {
+                    holder.token == token
+                        || (holder.account.is_some() && holder.account == username)
+                }
+                None => false,
+            })
+    }
+
+    /** Whether `token` is the token of a seat in any lobby.
+     */
+    pub(crate) fn is_seat_token(&self, token: &str) -> bool {
+        let holders = self.holders.lock().unwrap();
+        holders
+            .values()
+            .flatten()
+            .flatten()
+            .any(|holder| holder.token == token)
+    }
+
+    /** Lobbies where the account holds a seat, with the seat.
+     */
+    pub(crate) fn account_seats(&self, username: &str) -> Vec<(String, usize)> {
+        let holders = self.holders.lock().unwrap();
+        holders
+            .values()
+            .flatten()
+            .flatten()
+            .filter(|holder| holder.account.as_deref() == Some(username))
+            .map(|holder| (holder.lobby_id.clone(), holder.seat))
+            .collect()
+    }
+}
+
+/** The token of the `Authorization: Bearer <token>` header, a session or a seat token.
+ * Handlers taking one answer 401 when it is missing.
+ */
+pub(crate) struct BearerToken(pub(crate) String);
+
+impl FromRequest for BearerToken {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(bearer_token(req).map(|token| BearerToken(token.to_string())))
+    }
+}
+
+fn bearer_token(req: &HttpRequest) -> Result<&str, ApiError> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .ok_or_else(|| ApiError::Unauthorized("missing bearer token".to_string()))
+}
+
+/** The logged-in player making the request. Handlers taking one answer 401 to guests.
+ */
+pub(crate) struct Player {
+    pub(crate) username: String,
+    token: String,
+}
+
+impl FromRequest for Player {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(bearer_token(req).and_then(|token| authenticate(req, token)))
+    }
+}
+
+fn authenticate(req: &HttpRequest, token: &str) -> Result<Player, ApiError> {
+    let sessions = req
+        .app_data::<web::Data<Sessions>>()
+        .expect("sessions are registered as app data");
+    match sessions.username(token) {
+        Some(username) => Ok(Player {
+            username,
+            token: token.to_string(),
+        }),
+        None => Err(ApiError::Unauthorized(
+            "unknown or expired token".to_string(),
+        )),
+    }
+}
+
+/** The logged-in player making the request, if any. Guests send no `Authorization` header, or
+ * the token of a seat they already hold; any other token is answered with 401.
+ */
+pub(crate) struct MaybePlayer {
+    pub(crate) player: Option<Player>,
+    /** The session or seat token sent, if any. */
+    pub(crate) token: Option<String>,
+    accounts: web::Data<dyn AccountRepository>,
+}
+
+impl MaybePlayer {
+    /** Name to show for the seat and the account taking it: the username when logged in, or
+     * else the guest's chosen name, which can't be the username of an account.
+     */
+    pub(crate) async fn seat_name(
+        &self,
+        guest_name: Option<&str>,
+    ) -> Result<(String, Option<String>), ApiError> {
+        if let Some(player) = &self.player {
+            return Ok((player.username.clone(), Some(player.username.clone())));
+        }
+        let name: String = guest_name
+            .unwrap_or_default()
+            .trim()
+            .chars()
+            .take(MAX_USERNAME_LENGTH)
+            .collect();
+        if name.is_empty() {
+            return Ok((GUEST_NAME.to_string(), None));
+        }
+        let accounts = self.accounts.clone();
+        let (account, name) =
+            storage::blocking(move || accounts.get_account(&name).map(|account| (account, name)))
+                .await?;
+        match account {
+            Some(account) => Err(ApiError::UsernameTaken(account.username)),
+            None => Ok((name, None)),
+        }
+    }
+}
+
+impl FromRequest for MaybePlayer {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let accounts = req
+            .app_data::<web::Data<dyn AccountRepository>>()
+            .expect("accounts are registered as app data")
+            .clone();
+        if req.headers().get(header::AUTHORIZATION).is_none() {
+            return ready(Ok(MaybePlayer {
+                player: None,
+                token: None,
+                accounts,
+            }));
+        }
+        ready(bearer_token(req).and_then(|token| {
+            let seats = req
+                .app_data::<web::Data<Seats>>()
+                .expect("seats are registered as app data");
+            let player = match authenticate(req, token) {
+                Ok(player) => Some(player),
+                Err(_) if seats.is_seat_token(token) => None,
+                Err(err) => return Err(err),
+            };
+            Ok(MaybePlayer {
+                player,
+                token: Some(token.to_string()),
+                accounts,
+            })
+        }))
+    }
+}
+
+#[post("/user/register")]
+async fn register(
+    accounts: web::Data<dyn AccountRepository>,
+    sessions: web::Data<Sessions>,
+    body: web::Json<Credentials>,
+) -> Result<HttpResponse, ApiError> {
+    let Credentials { username, password } = body.into_inner();
+    validate(&username, &password)?;
+    // Guests who chose no name show as this one
+    if username == GUEST_NAME {
+        return Err(ApiError::UsernameTaken(username));
+    }
+    // Argon2 is slow on purpose, keep it off the async workers
+    let password_hash = web::block(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+    })
+    .await
+    .map_err(|err| ApiError::Internal(err.to_string()))?
+    .map_err(|err| ApiError::Internal(err.to_string()))?;
+    let account = Account {
+        username,
+        password_hash,
+        created_at: crate::now(),
+    };
+    let accounts = accounts.clone();
+    let (created, account) = storage::blocking(move || {
+        accounts
+            .create_account(&account)
+            .map(|created| (created, account))
+    })
+    .await?;
+    if !created {
+        return Err(ApiError::UsernameTaken(account.username));
+    }
+    Ok(HttpResponse::Created().json(sessions.open(&account.username)))
+}
+
+#[post("/user/login")]
+async fn login(
+    accounts: web::Data<dyn AccountRepository>,
+    sessions: web::Data<Sessions>,
+    body: web::Json<Credentials>,
+) -> Result<HttpResponse, ApiError> {
+    let Credentials { username, password } = body.into_inner();
+    let accounts = accounts.clone();
+    let account = storage::blocking(move || accounts.get_account(&username)).await?;
+    let password_hash = account
+        .as_ref()
+        .map(|account| account.password_hash.clone());
+    let verified = web::block(move || {
+        // Unknown usernames are checked against a dummy hash, or they would be told apart by
+        // how fast the answer comes
+        let password_hash = password_hash.as_deref().unwrap_or_else(|| dummy_hash());
+        PasswordHash::new(password_hash).map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+    })
+    .await
+    .map_err(|err| ApiError::Internal(err.to_string()))?
+    .map_err(|err| ApiError::Internal(err.to_string()))?;
+    match account {
+        Some(account) if verified => Ok(HttpResponse::Ok().json(sessions.open(&account.username))),
+        _ => Err(ApiError::Unauthorized(
+            "wrong username or password".to_string(),
+        )),
+    }
+}
+
+#[post("/user/logout")]
+async fn logout(sessions: web::Data<Sessions>, player: Player) -> HttpResponse {
+    sessions.close(&player.token);
+    HttpResponse::NoContent().finish()
+}
+
+fn validate(username: &str, password: &str) -> Result<(), ApiError> {
+    let valid_username = !username.is_empty()
+        && username.chars().count() <= MAX_USERNAME_LENGTH
+        && username
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.');
+    if !valid_username {
+        return Err(ApiError::InvalidRequest(format!(
+            "usernames have 1 to {MAX_USERNAME_LENGTH} letters, digits, '_', '-' or '.'"
+        )));
+    }
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(ApiError::InvalidRequest(format!(
+            "passwords have at least {MIN_PASSWORD_LENGTH} characters"
+        )));
+    }
+    Ok(())
+}
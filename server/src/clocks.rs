@@ -0,0 +1,62 @@
+use actix_web::rt::time;
+use actix_web::web;
+use std::time::Duration;
+
+use crate::errors::ApiError;
+use crate::events::LobbyEvents;
+use crate::specs::{lock_lobby, LobbyRegistry, SharedLobby};
+use crate::storage::GameRepository;
+
+/** How often running clocks are checked, the most a flag fall can go unnoticed when nobody
+ * moves.
+ */
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/** Ends the games whose side to move has run out of time, without waiting for its next move.
+ */
+pub(crate) async fn watch_flags(
+    lobbies: web::Data<LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+    repository: web::Data<dyn GameRepository>,
+) {
+    let mut interval = time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = crate::now_ms();
+        for lobby in lobbies.all() {
+            let game = {
+                let mut game = lock_lobby(&lobby);
+                if !game.flag_fall(now) {
+                    continue;
+                }
+                events.publish_game_over(&game);
+                game.clone()
+            };
+            let id = game.id.clone();
+            if let Err(err) = lobbies.store(&repository, game).await {
+                eprintln!("Could not save lobby {id}: {err}");
+            }
+        }
+    }
+}
+
+/** Ends the game if the side to move has run out of time and the watcher hasn't noticed yet,
+ * answering [`ApiError::GameOver`] then, so a request never acts for a player whose flag fell.
+ */
+pub(crate) async fn check_flag(
+    lobby: &SharedLobby,
+    lobbies: &LobbyRegistry,
+    events: &LobbyEvents,
+    repository: &web::Data<dyn GameRepository>,
+) -> Result<(), ApiError> {
+    let game = {
+        let mut game = lock_lobby(lobby);
+        if !game.flag_fall(crate::now_ms()) {
+            return Ok(());
+        }
+        events.publish_game_over(&game);
+        game.clone()
+    };
+    lobbies.store(repository, game).await?;
+    Err(ApiError::GameOver)
+}
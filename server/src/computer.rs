@@ -0,0 +1,98 @@
+use actix_web::web;
+use chess_core::engine;
+use chess_core::specs::{ChessGame, LobbyEvent};
+
+use crate::events::LobbyEvents;
+use crate::specs::{lock_lobby, LobbyRegistry, SharedLobby};
+use crate::storage::GameRepository;
+
+/** Name shown in the seat the computer plays from.
+ */
+pub(crate) const COMPUTER_NAME: &str = "Computer";
+
+/** Plays the computer's move if it is its turn. The search runs on the blocking pool without
+ * holding the lobby, and its move is dropped if the game moved on meanwhile, e.g. the player
+ * resigned.
+ */
+pub(crate) async fn reply(
+    lobby: SharedLobby,
+    lobbies: web::Data<LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+    repository: web::Data<dyn GameRepository>,
+) {
+    let (board, strength) = {
+        let game = lock_lobby(&lobby);
+        match game.computer_to_move() {
+            Some(strength) => (game.board.clone(), strength),
+            None => return,
+        }
+    };
+    let ply = board.moves.len();
+    let mv = match web::block(move || engine::best_move(&board, strength.limits())).await {
+        Ok(Some(mv)) => mv,
+        Ok(None) => return,
+        Err(err) => {
+            eprintln!("Computer move search failed: {err}");
+            return;
+        }
+    };
+
+    let game = {
+        let mut game = lock_lobby(&lobby);
+        if game.computer_to_move().is_none() || game.board.moves.len() != ply {
+            return;
+        }
+        let now = crate::now_ms();
+        if game.flag_fall(now) {
+            events.publish_game_over(&game);
+        } else {
+            let san = game.board.to_san(&mv);
+            game.press_clock(now);
+            game.move_piece(mv);
+            events.publish(
+                &game.id,
+                LobbyEvent::Move {
+                    san,
+                    game: game.clone(),
+                },
+            );
+            events.publish_game_over(&game);
+        }
+        game.clone()
+    };
+    let id = game.id.clone();
+    if let Err(err) = lobbies.store(&repository, game).await {
+        eprintln!("Could not save lobby {id}: {err}");
+    }
+}
+
+/** Answers a draw offered to the computer right away: it takes the draw only when its side of
+ * the board evaluates worse.
+ */
+pub(crate) fn answer_draw(game: &mut ChessGame, events: &LobbyEvents) {
+    let color = game.seat_color(1);
+    let mut score = engine::evaluate(&game.board);
+    if game.board.turn != color {
+        score = -score;
+    }
+    let accept = score < 0;
+    let answer = if accept {
+        game.accept_draw(color)
+    } else {
+        game.decline_draw(color)
+    };
+    if answer.is_err() {
+        return;
+    }
+    if accept {
+        events.publish_game_over(game);
+    } else {
+        events.publish(
+            &game.id,
+            LobbyEvent::DrawDeclined {
+                player: COMPUTER_NAME.to_string(),
+                game: game.clone(),
+            },
+        );
+    }
+}
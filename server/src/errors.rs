@@ -0,0 +1,203 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use chess_core::specs::{ApiErrorBody, ErrorCode};
+use std::fmt;
+
+use crate::storage::StorageError;
+
+/** Error returned by the handlers, answered with an [`ApiErrorBody`] and the matching status.
+ */
+#[derive(Debug)]
+pub(crate) enum ApiError {
+    InvalidRequest(String),
+    LobbyNotFound(String),
+    NotAPlayer(String),
+    LobbyFull(String),
+    NotYourTurn,
+    GameOver,
+    IllegalMove(String),
+    Unauthorized(String),
+    UsernameTaken(String),
+    DeadlineNotPassed,
+    AlreadySeated,
+    InvalidState(String),
+    Storage(StorageError),
+    Internal(String),
+}
+
+impl From<StorageError> for ApiError {
+    fn from(err: StorageError) -> Self {
+        ApiError::Storage(err)
+    }
+}
+
+impl ApiError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            ApiError::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            ApiError::LobbyNotFound(_) => ErrorCode::LobbyNotFound,
+            ApiError::NotAPlayer(_) => ErrorCode::NotAPlayer,
+            ApiError::LobbyFull(_) => ErrorCode::LobbyFull,
+            ApiError::NotYourTurn => ErrorCode::NotYourTurn,
+            ApiError::GameOver => ErrorCode::GameOver,
+            ApiError::IllegalMove(_) => ErrorCode::IllegalMove,
+            ApiError::Unauthorized(_) => ErrorCode::Unauthorized,
+            ApiError::UsernameTaken(_) => ErrorCode::UsernameTaken,
+            ApiError::DeadlineNotPassed => ErrorCode::DeadlineNotPassed,
+            ApiError::AlreadySeated => ErrorCode::AlreadySeated,
+            ApiError::InvalidState(_) => ErrorCode::InvalidState,
+            ApiError::Storage(_) | ApiError::Internal(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::InvalidRequest(reason) => write!(f, "Invalid request: {reason}"),
+            ApiError::LobbyNotFound(id) => write!(f, "Lobby {id} not found!"),
+            ApiError::NotAPlayer(lobby) => write!(f, "You have no seat in lobby {lobby}!"),
+            ApiError::LobbyFull(lobby) => write!(f, "Lobby {lobby} is full!"),
+            ApiError::NotYourTurn => write!(f, "It's not your turn!"),
+            ApiError::GameOver => write!(f, "The game is over!"),
+            ApiError::IllegalMove(reason) => write!(f, "Invalid move! {reason}"),
+            ApiError::Unauthorized(reason) => write!(f, "Unauthorized: {reason}"),
+            ApiError::UsernameTaken(username) => write!(f, "Username {username} is already taken!"),
+            ApiError::DeadlineNotPassed => {
+                write!(f, "Your opponent's move deadline hasn't passed!")
+            }
+            ApiError::AlreadySeated => write!(f, "You already sit in this lobby!"),
+            ApiError::InvalidState(reason) => write!(f, "Not possible now: {reason}"),
+            ApiError::Storage(err) => write!(f, "{err}"),
+            ApiError::Internal(reason) => write!(f, "Internal error: {reason}"),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::LobbyNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::NotAPlayer(_) => StatusCode::FORBIDDEN,
+            ApiError::LobbyFull(_) | ApiError::NotYourTurn | ApiError::GameOver => {
+                StatusCode::CONFLICT
+            }
+            ApiError::IllegalMove(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::UsernameTaken(_)
+            | ApiError::DeadlineNotPassed
+            | ApiError::AlreadySeated
+            | ApiError::InvalidState(_) => StatusCode::CONFLICT,
+            ApiError::Storage(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ApiErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[test]
+    fn maps_every_error_to_its_status_and_code() {
+        for (error, status, code) in [
+            (
+                ApiError::InvalidRequest(String::new()),
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidRequest,
+            ),
+            (
+                ApiError::LobbyNotFound(String::new()),
+                StatusCode::NOT_FOUND,
+                ErrorCode::LobbyNotFound,
+            ),
+            (
+                ApiError::NotAPlayer(String::new()),
+                StatusCode::FORBIDDEN,
+                ErrorCode::NotAPlayer,
+            ),
+            (
+                ApiError::LobbyFull(String::new()),
+                StatusCode::CONFLICT,
+                ErrorCode::LobbyFull,
+            ),
+            (
+                ApiError::NotYourTurn,
+                StatusCode::CONFLICT,
+                ErrorCode::NotYourTurn,
+            ),
+            (
+                ApiError::GameOver,
+                StatusCode::CONFLICT,
+                ErrorCode::GameOver,
+            ),
+            (
+                ApiError::IllegalMove(String::new()),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::IllegalMove,
+            ),
+            (
+                ApiError::Unauthorized(String::new()),
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
+            ),
+            (
+                ApiError::UsernameTaken(String::new()),
+                StatusCode::CONFLICT,
+                ErrorCode::UsernameTaken,
+            ),
+            (
+                ApiError::DeadlineNotPassed,
+                StatusCode::CONFLICT,
+                ErrorCode::DeadlineNotPassed,
+            ),
+            (
+                ApiError::AlreadySeated,
+                StatusCode::CONFLICT,
+                ErrorCode::AlreadySeated,
+            ),
+            (
+                ApiError::InvalidState(String::new()),
+                StatusCode::CONFLICT,
+                ErrorCode::InvalidState,
+            ),
+            (
+                ApiError::Storage(StorageError(String::new())),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::Internal,
+            ),
+            (
+                ApiError::Internal(String::new()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::Internal,
+            ),
+        ] {
+            assert_eq!(error.status_code(), status, "{error:?}");
+            assert_eq!(error.code(), code, "{error:?}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn answers_with_the_error_body() {
+        let error = ApiError::IllegalMove("e2e5 isn't legal here".to_string());
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let body: ApiErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            ApiErrorBody {
+                code: ErrorCode::IllegalMove,
+                message: "Invalid move! e2e5 isn't legal here".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use chess_core::specs::{ChessGame, LobbyEvent, LobbyState};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/** How many events a slow subscriber may fall behind before it starts missing some.
+ */
+const CHANNEL_CAPACITY: usize = 64;
+
+/** One broadcast channel per lobby someone listens to, created on the first subscription and
+ * dropped once the lobby's last event is sent or its subscribers are gone, plus one carrying
+ * the events of every lobby.
+ */
+pub(crate) struct LobbyEvents {
+    channels: Mutex<HashMap<String, broadcast::Sender<LobbyEvent>>>,
+    all: broadcast::Sender<LobbyEvent>,
+}
+
+impl Default for LobbyEvents {
+    fn default() -> Self {
+        Self {
+            channels: Mutex::new(HashMap::new()),
+            all: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+}
+
+impl LobbyEvents {
+    pub(crate) fn subscribe(&self, lobby_id: &str) -> broadcast::Receiver<LobbyEvent> {
+        let mut channels = self.channels.lock().unwrap();
+        channels.retain(|_, sender| sender.receiver_count() > 0);
+        channels
+            .entry(lobby_id.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    pub(crate) fn subscribe_all(&self) -> broadcast::Receiver<LobbyEvent> {
+        self.all.subscribe()
+    }
+
+    /** Sends `event` to the current subscribers of the lobby and of all lobbies, if any.
+     */
+    pub(crate) fn publish(&self, lobby_id: &str, event: LobbyEvent) {
+        {
+            let mut channels = self.channels.lock().unwrap();
+            if let Some(sender) = channels.get(lobby_id) {
+                // Only fails when nobody is listening
+                let _ = sender.send(event.clone());
+                if sender.receiver_count() == 0 || is_last(&event) {
+                    channels.remove(lobby_id);
+                }
+            }
+        }
+        let _ = self.all.send(event);
+    }
+
+    /** Publishes [`LobbyEvent::GameOver`] if the game has ended.
+     */
+    pub(crate) fn publish_game_over(&self, game: &ChessGame) {
+        if let (Some(result), Some(reason)) = (game.result, game.end_reason) {
+            self.publish(
+                &game.id,
+                LobbyEvent::GameOver {
+                    result,
+                    reason,
+                    game: game.clone(),
+                },
+            );
+        }
+    }
+}
+
+/** Nothing happens in a lobby after its game is over or it is closed.
+ */
+fn is_last(event: &LobbyEvent) -> bool {
+    matches!(event, LobbyEvent::GameOver { .. }) || event.game().state == LobbyState::Aborted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess_core::data::Color;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    fn game(id: &str) -> ChessGame {
+        let mut game = ChessGame::new(Color::White);
+        game.id = id.to_string();
+        game
+    }
+
+    #[test]
+    fn publishing_without_subscribers_keeps_no_channel() {
+        let events = LobbyEvents::default();
+        events.publish("a", LobbyEvent::Created { game: game("a") });
+        assert!(events.channels.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn drops_the_channel_once_its_subscribers_are_gone() {
+        let events = LobbyEvents::default();
+        drop(events.subscribe("a"));
+        let b = events.subscribe("b");
+        assert_eq!(events.channels.lock().unwrap().len(), 1);
+        drop(b);
+        events.publish("b", LobbyEvent::Created { game: game("b") });
+        assert!(events.channels.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn drops_the_channel_after_the_last_event() {
+        let events = LobbyEvents::default();
+        let mut receiver = events.subscribe("b");
+        let mut aborted = game("b");
+        aborted.state = LobbyState::Aborted;
+        events.publish(
+            "b",
+            LobbyEvent::Abort {
+                player: String::new(),
+                game: aborted,
+            },
+        );
+        assert!(events.channels.lock().unwrap().is_empty());
+        assert!(matches!(receiver.try_recv(), Ok(LobbyEvent::Abort { .. })));
+        assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Closed);
+    }
+}
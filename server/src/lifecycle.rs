@@ -0,0 +1,189 @@
+use actix_web::dev::Payload;
+use actix_web::{post, web, FromRequest, HttpRequest, HttpResponse};
+use chess_core::specs::{ChessGame, LobbyAction, LobbyEvent};
+use std::future::{ready, Ready};
+
+use crate::auth::{BearerToken, Seats, Sessions};
+use crate::errors::ApiError;
+use crate::events::LobbyEvents;
+use crate::specs::{lock_lobby, LobbyRegistry};
+use crate::storage::GameRepository;
+
+/** Gives up the seat before the game starts. The creator leaving closes the lobby, the second
+ * player leaving reopens it and their token stops working.
+ */
+#[post("/leave-lobby")]
+async fn leave_lobby(
+    lifecycle: Lifecycle,
+    token: BearerToken,
+    body: web::Json<LobbyAction>,
+) -> Result<HttpResponse, ApiError> {
+    let (game, seat) = lifecycle
+        .act(
+            &body.id,
+            &token,
+            |lobby, seat| lobby.leave(seat),
+            |player, game| Some(LobbyEvent::Leave { player, game }),
+        )
+        .await?;
+    lifecycle.seats.release(&game.id, seat).await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+/** Ends a game nobody has moved in yet, without a result.
+ */
+#[post("/abort")]
+async fn abort(
+    lifecycle: Lifecycle,
+    token: BearerToken,
+    body: web::Json<LobbyAction>,
+) -> Result<HttpResponse, ApiError> {
+    let (game, _) = lifecycle
+        .act(
+            &body.id,
+            &token,
+            |lobby, _| lobby.abort(),
+            |player, game| Some(LobbyEvent::Abort { player, game }),
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+#[post("/resign")]
+async fn resign(
+    lifecycle: Lifecycle,
+    token: BearerToken,
+    body: web::Json<LobbyAction>,
+) -> Result<HttpResponse, ApiError> {
+    let (game, _) = lifecycle
+        .act(
+            &body.id,
+            &token,
+            |lobby, seat| lobby.resign(lobby.seat_color(seat)),
+            |player, game| Some(LobbyEvent::Resign { player, game }),
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+/** The offer stands until the opponent accepts, declines or moves, the computer answers at once.
+ */
+#[post("/offer-draw")]
+async fn offer_draw(
+    lifecycle: Lifecycle,
+    token: BearerToken,
+    body: web::Json<LobbyAction>,
+) -> Result<HttpResponse, ApiError> {
+    let (game, _) = lifecycle
+        .act(
+            &body.id,
+            &token,
+            |lobby, seat| lobby.offer_draw(lobby.seat_color(seat)),
+            |player, game| Some(LobbyEvent::DrawOffer { player, game }),
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+#[post("/accept-draw")]
+async fn accept_draw(
+    lifecycle: Lifecycle,
+    token: BearerToken,
+    body: web::Json<LobbyAction>,
+) -> Result<HttpResponse, ApiError> {
+    let (game, _) = lifecycle
+        .act(
+            &body.id,
+            &token,
+            |lobby, seat| lobby.accept_draw(lobby.seat_color(seat)),
+            |_, _| None,
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+#[post("/decline-draw")]
+async fn decline_draw(
+    lifecycle: Lifecycle,
+    token: BearerToken,
+    body: web::Json<LobbyAction>,
+) -> Result<HttpResponse, ApiError> {
+    let (game, _) = lifecycle
+        .act(
+            &body.id,
+            &token,
+            |lobby, seat| lobby.decline_draw(lobby.seat_color(seat)),
+            |player, game| Some(LobbyEvent::DrawDeclined { player, game }),
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+/** What the lifecycle requests share: the lobbies, their events and storage, and who sits where.
+ */
+struct Lifecycle {
+    lobbies: web::Data<LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+    repository: web::Data<dyn GameRepository>,
+    seats: web::Data<Seats>,
+    sessions: web::Data<Sessions>,
+}
+
+impl Lifecycle {
+    /** Ends the game first if the flag of the side to move fell, then applies `action` for the
+     * seat `token` holds and publishes the event `event` makes of the acting player's name and
+     * the game, followed by the game over if it ended. A draw offered to the computer is
+     * answered right away. The game is saved once the lobby is unlocked, and returned with the
+     * seat.
+     */
+    async fn act(
+        &self,
+        id: &str,
+        token: &BearerToken,
+        action: impl FnOnce(&mut ChessGame, usize) -> Result<(), String>,
+        event: impl FnOnce(String, ChessGame) -> Option<LobbyEvent>,
+    ) -> Result<(ChessGame, usize), ApiError> {
+        let lobby = crate::find_lobby(&self.lobbies, &self.repository, id).await?;
+        crate::clocks::check_flag(&lobby, &self.lobbies, &self.events, &self.repository).await?;
+        let (game, seat) = {
+            let mut lobby = lock_lobby(&lobby);
+            let seat = self
+                .seats
+                .seat_of(&lobby.id, &token.0, &self.sessions)
+                .ok_or_else(|| ApiError::NotAPlayer(lobby.id.clone()))?;
+            // Taken before acting, the second player's name is gone once they leave
+            let player = lobby.seat_player(seat).to_string();
+            action(&mut lobby, seat).map_err(ApiError::InvalidState)?;
+            if let Some(event) = event(player, lobby.clone()) {
+                self.events.publish(&lobby.id, event);
+            }
+            self.events.publish_game_over(&lobby);
+            if lobby.computer.is_some() && lobby.draw_offer.is_some() {
+                crate::computer::answer_draw(&mut lobby, &self.events);
+            }
+            (lobby.clone(), seat)
+        };
+        let game = self.lobbies.store(&self.repository, game).await?;
+        Ok((game, seat))
+    }
+}
+
+impl FromRequest for Lifecycle {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        fn data<T: ?Sized + 'static>(req: &HttpRequest) -> web::Data<T> {
+            req.app_data::<web::Data<T>>()
+                .expect("the lobby services are registered as app data")
+                .clone()
+        }
+        ready(Ok(Lifecycle {
+            lobbies: data(req),
+            events: data(req),
+            repository: data(req),
+            seats: data(req),
+            sessions: data(req),
+        }))
+    }
+}
@@ -1,12 +1,31 @@
-use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder, http};
-use std::sync::Mutex;
+use actix_web::http::header::{self, EntityTag};
+use actix_web::{get, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder, http};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 use actix_cors::Cors;
-use crate::specs::{JoinLobby, UpdateReq};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chess_core::clock::{Clock, Correspondence};
+use chess_core::setups::SETUPS;
+use crate::auth::{BearerToken, MaybePlayer, Player, Seats, Sessions};
+use crate::errors::ApiError;
+use crate::events::LobbyEvents;
+use crate::specs::lock_lobby;
+use crate::storage::{AccountRepository, GameRepository, SeatRepository, StorageError};
+use chess_core::specs::{
+    self as chess_specs, ImportPgn, JoinLobby, LobbyAction, LobbyEvent, LobbyState,
+    MoveChessPiece, MoveRequestError, NewLobby, Seat,
+};
 
-
-
-mod data;
+mod auth;
+mod clocks;
+mod computer;
+mod errors;
+mod events;
+mod lifecycle;
 mod specs;
+mod sse;
+mod storage;
+mod ws;
 
 #[get("/")]
 async fn hello() -> impl Responder {
@@ -16,93 +35,388 @@ async fn hello() -> impl Responder {
 // !INFO Useful params: https://actix.rs/docs/extractors/
 #[post("/new-lobby")]
 async fn new_lobby(
-    lobbies: web::Data<specs::LobbiesMutex>,
-    body: web::Json<String>,
-) -> impl Responder {
-    let mut lobbies = lobbies.lobbies.lock().unwrap();
-    let mut new_lobby = specs::ChessGame::new(data::Color::White);
-    new_lobby.load_new();
-    new_lobby.player1 = body.to_string();
-    let id = uuid::Uuid::new_v4().to_string();
-    new_lobby.id = id.clone();
-    lobbies.push(new_lobby);
-    HttpResponse::Ok().body(format!("New lobby created for {body}! ID: {id}"))
+    lobbies: web::Data<specs::LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+    repository: web::Data<dyn GameRepository>,
+    seats: web::Data<Seats>,
+    player: MaybePlayer,
+    body: web::Json<NewLobby>,
+) -> Result<HttpResponse, ApiError> {
+    let mut new_lobby = chess_specs::ChessGame::with_color_choice(body.color);
+    body.starting_fen()
+        .and_then(|fen| new_lobby.load_fen(fen))
+        .map_err(|err| ApiError::InvalidRequest(format!("invalid starting position, {err}")))?;
+    // A lobby has to start with a game left to play
+    if new_lobby.is_over {
+        let reason = if new_lobby.board.get_all_moves().is_empty() {
+            "position has no legal moves"
+        } else {
+            "position is already drawn"
+        };
+        return Err(ApiError::InvalidRequest(reason.to_string()));
+    }
+    let (name, account) = player.seat_name(body.player.as_deref()).await?;
+    if let Some(control) = body.time_control {
+        control
+            .validate()
+            .map_err(|err| ApiError::InvalidRequest(format!("invalid time control, {err}")))?;
+        new_lobby.clock = Some(Clock::new(control));
+    }
+    if let Some(days) = body.days_per_move {
+        if new_lobby.clock.is_some() {
+            return Err(ApiError::InvalidRequest(
+                "a game has either a time control or days per move".to_string(),
+            ));
+        }
+        let correspondence = Correspondence::new(days)
+            .map_err(|err| ApiError::InvalidRequest(format!("invalid days per move, {err}")))?;
+        new_lobby.correspondence = Some(correspondence);
+    }
+    new_lobby.player1 = name;
+    new_lobby.created_at = now();
+    new_lobby.id = uuid::Uuid::new_v4().to_string();
+    if let Some(strength) = body.computer {
+        new_lobby.computer = Some(strength);
+        new_lobby.player2 = computer::COMPUTER_NAME.to_string();
+        new_lobby.start(now_ms(), coin_flip());
+    }
+    let new_lobby = lobbies.store(&repository, new_lobby).await?;
+    let token = seats.take(&new_lobby.id, 0, account).await?;
+    lobbies.insert(new_lobby.clone());
+    events.publish(
+        &new_lobby.id,
+        LobbyEvent::Created {
+            game: new_lobby.clone(),
+        },
+    );
+    // Opens the game when the creator plays Black
+    if let Some(lobby) = lobbies.get(&new_lobby.id) {
+        actix_web::rt::spawn(computer::reply(
+            lobby,
+            lobbies.clone(),
+            events.clone(),
+            repository.clone(),
+        ));
+    }
+    Ok(HttpResponse::Created().json(Seat {
+        token,
+        game: new_lobby,
+    }))
+}
+
+#[post("/games/import")]
+async fn import_pgn(
+    lobbies: web::Data<specs::LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+    repository: web::Data<dyn GameRepository>,
+    seats: web::Data<Seats>,
+    player: MaybePlayer,
+    body: web::Json<ImportPgn>,
+) -> Result<HttpResponse, ApiError> {
+    let mut game = chess_specs::ChessGame::from_pgn(&body.pgn)
+        .map_err(|err| ApiError::InvalidRequest(format!("invalid PGN, {err}")))?;
+    let (name, account) = player.seat_name(body.player.as_deref()).await?;
+    game.player1 = name;
+    game.created_at = now();
+    game.id = uuid::Uuid::new_v4().to_string();
+    let game = lobbies.store(&repository, game).await?;
+    let token = seats.take(&game.id, 0, account).await?;
+    // A finished game is only kept in the repository
+    if !game.is_over {
+        lobbies.insert(game.clone());
+    }
+    events.publish(&game.id, LobbyEvent::Created { game: game.clone() });
+    Ok(HttpResponse::Created().json(Seat { token, game }))
+}
+
+#[get("/setups")]
+async fn get_setups() -> impl Responder {
+    let names: Vec<&str> = SETUPS.iter().map(|(name, _)| *name).collect();
+    HttpResponse::Ok().json(names)
 }
 
 #[post("/join-lobby")]
 async fn join_lobby(
-    lobbies: web::Data<specs::LobbiesMutex>,
+    lobbies: web::Data<specs::LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+    repository: web::Data<dyn GameRepository>,
+    seats: web::Data<Seats>,
+    sessions: web::Data<Sessions>,
+    player: MaybePlayer,
     body: web::Json<JoinLobby>,
-) -> impl Responder {
-    let mut lobbies = lobbies.lobbies.lock().unwrap();
-    let mut lobby_iter = lobbies.iter_mut();
-    let lobby = lobby_iter.find(|lobby| lobby.id == body.id.to_string());
-    if lobby.is_none() {
-        return HttpResponse::Ok().body(format!("Lobby {} not found!", body.id));
-    } else {
-        let unwrapped = lobby.unwrap();
-        unwrapped.player2 = body.player.to_string();
-        return HttpResponse::Ok().json(unwrapped);
-    }
+) -> Result<HttpResponse, ApiError> {
+    let lobby = find_lobby(&lobbies, &repository, &body.id).await?;
+    let (name, account) = player.seat_name(body.player.as_deref()).await?;
+    let game = {
+        let mut lobby = lock_lobby(&lobby);
+        match lobby.state {
+            LobbyState::Waiting => {}
+            LobbyState::InProgress => return Err(ApiError::LobbyFull(body.id.to_string())),
+            LobbyState::Finished | LobbyState::Aborted => return Err(ApiError::GameOver),
+        }
+        // The creator's seat token, or the session of the account that took the seat
+        if let Some(token) = &player.token {
+            if seats.seat_of(&lobby.id, token, &sessions) == Some(0) {
+                return Err(ApiError::AlreadySeated);
+            }
+        }
+        lobby.player2 = name.clone();
+        lobby.start(now_ms(), coin_flip());
+        lobby.version += 1;
+        events.publish(
+            &lobby.id,
+            LobbyEvent::Join {
+                player: name,
+                game: lobby.clone(),
+            },
+        );
+        lobby.clone()
+    };
+    let game = lobbies.store(&repository, game).await?;
+    let token = seats.take(&game.id, 1, account).await?;
+    Ok(HttpResponse::Ok().json(Seat { token, game }))
 }
 
 #[post("/move")]
 async fn move_piece(
-    lobbies: web::Data<specs::LobbiesMutex>,
-    body: web::Json<specs::MoveChessPiece>,
-) -> impl Responder {
-    let mut lobbies = lobbies.lobbies.lock().unwrap();
-    let mut lobby_iter = lobbies.iter_mut();
-    let lobby = lobby_iter.find(|lobby| lobby.id == body.id.to_string());
-    if lobby.is_none() {
-        return HttpResponse::Ok().body(format!("Lobby {} not found!", body.id));
-    } else {
-        let lobby = lobby.unwrap();
-        if body.player != lobby.player1 && body.player != lobby.player2 {
-            return HttpResponse::Ok().body(format!(
-                "Player {} not found in lobby {}!",
-                body.player, body.id
-            ));
+    lobbies: web::Data<specs::LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+    repository: web::Data<dyn GameRepository>,
+    seats: web::Data<Seats>,
+    sessions: web::Data<Sessions>,
+    token: BearerToken,
+    body: web::Json<MoveChessPiece>,
+) -> Result<HttpResponse, ApiError> {
+    let shared = find_lobby(&lobbies, &repository, &body.id).await?;
+    clocks::check_flag(&shared, &lobbies, &events, &repository).await?;
+    let game = {
+        let mut lobby = lock_lobby(&shared);
+        let seat = seats
+            .seat_of(&lobby.id, &token.0, &sessions)
+            .ok_or_else(|| ApiError::NotAPlayer(body.id.to_string()))?;
+        if lobby.is_over {
+            return Err(ApiError::GameOver);
         }
-
-        if body.player == lobby.player1 && lobby.color_player_1 != lobby.board.turn {
-            return HttpResponse::Ok().body(format!("It's not your turn!"));
+        if lobby.state == LobbyState::Waiting {
+            return Err(ApiError::InvalidState("waiting for an opponent".to_string()));
         }
 
-        if body.player == lobby.player2 && lobby.color_player_1 == lobby.board.turn {
-            return HttpResponse::Ok().body(format!("It's not your turn!"));
+        if lobby.seat_color(seat) != lobby.board.turn {
+            return Err(ApiError::NotYourTurn);
         }
 
-        if lobby.validify_move(body.from, body.to) {
-            lobby.move_piece(body.from, body.to);
-            return HttpResponse::Ok().json(lobby);
-        } else {
-            return HttpResponse::Ok().body(format!("Invalid move!"));
+        let mv = body.get_move(&lobby.board).map_err(|err| match err {
+            MoveRequestError::Malformed(reason) => ApiError::InvalidRequest(reason),
+            MoveRequestError::Unreadable(reason) => ApiError::IllegalMove(reason),
+        })?;
+        if !lobby.validify_move(&mv) {
+            return Err(ApiError::IllegalMove(format!("{} isn't legal here", mv.to_uci())));
         }
+        let san = lobby.board.to_san(&mv);
+        lobby.press_clock(now_ms());
+        lobby.move_piece(mv);
+        events.publish(
+            &lobby.id,
+            LobbyEvent::Move {
+                san,
+                game: lobby.clone(),
+            },
+        );
+        events.publish_game_over(&lobby);
+        lobby.clone()
+    };
+    if game.computer.is_some() {
+        actix_web::rt::spawn(computer::reply(
+            shared,
+            lobbies.clone(),
+            events.clone(),
+            repository.clone(),
+        ));
     }
+    let game = lobbies.store(&repository, game).await?;
+    Ok(HttpResponse::Ok().json(game))
 }
 
-#[get("/lobbies")]
-async fn get_lobbies(lobbies: web::Data<specs::LobbiesMutex>) -> impl Responder {
-    let lobbies = lobbies.lobbies.lock().unwrap();
-    HttpResponse::Ok().json(&*lobbies)
-}
-
-#[get("/update")]
-async fn get_update(
-    lobbies: web::Data<specs::LobbiesMutex>,
-    body: web::Json<UpdateReq>
-) -> impl Responder {
-    let lobbies_vec = lobbies.lobbies.lock().unwrap();
-    let lobby = lobbies_vec.iter().find(|lobby| lobby.id == body.id.to_string());
-    match lobby {
-        Some(lobby) => {
-            return HttpResponse::Ok().json(lobby);
+/** Wins a correspondence game whose opponent didn't move in time.
+ */
+#[post("/claim-win")]
+async fn claim_win(
+    lobbies: web::Data<specs::LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+    repository: web::Data<dyn GameRepository>,
+    seats: web::Data<Seats>,
+    sessions: web::Data<Sessions>,
+    token: BearerToken,
+    body: web::Json<LobbyAction>,
+) -> Result<HttpResponse, ApiError> {
+    let lobby = find_lobby(&lobbies, &repository, &body.id).await?;
+    let game = {
+        let mut lobby = lock_lobby(&lobby);
+        let seat = seats
+            .seat_of(&lobby.id, &token.0, &sessions)
+            .ok_or_else(|| ApiError::NotAPlayer(body.id.to_string()))?;
+        if lobby.is_over {
+            return Err(ApiError::GameOver);
+        }
+        let claimant = lobby.seat_color(seat);
+        if !lobby.claim_deadline(claimant, now_ms()) {
+            return Err(ApiError::DeadlineNotPassed);
         }
-        None => {
-            return HttpResponse::Ok().body(format!("Lobby {} not found!", body.id));
+        events.publish_game_over(&lobby);
+        lobby.clone()
+    };
+    let game = lobbies.store(&repository, game).await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+/** Ongoing games of the logged-in player waiting for their move, the closest correspondence
+ * deadline first.
+ */
+#[get("/user/your-turn")]
+async fn your_turn(
+    lobbies: web::Data<specs::LobbyRegistry>,
+    seats: web::Data<Seats>,
+    player: Player,
+) -> HttpResponse {
+    let mut games = vec![];
+    for (lobby_id, seat) in seats.account_seats(&player.username) {
+        let Some(lobby) = lobbies.get(&lobby_id) else {
+            continue;
+        };
+        let game = lock_lobby(&lobby);
+        if game.state == LobbyState::InProgress && game.seat_color(seat) == game.board.turn {
+            games.push(game.clone());
         }
     }
+    games.sort_by_key(|game| {
+        game.correspondence
+            .and_then(|correspondence| correspondence.deadline)
+            .unwrap_or(u64::MAX)
+    });
+    HttpResponse::Ok().json(games)
+}
+
+#[get("/lobbies")]
+async fn get_lobbies(lobbies: web::Data<specs::LobbyRegistry>) -> impl Responder {
+    HttpResponse::Ok().json(lobbies.snapshot())
+}
+
+/** The whole game, answered with `304 Not Modified` when the `If-None-Match` header already
+ * holds the current version, so clients can poll cheaply.
+ */
+#[get("/lobbies/{id}")]
+async fn get_lobby(
+    lobbies: web::Data<specs::LobbyRegistry>,
+    repository: web::Data<dyn GameRepository>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let lobby = find_lobby(&lobbies, &repository, &path).await?;
+    let lobby = lock_lobby(&lobby);
+    let etag = EntityTag::new_strong(lobby.version.to_string());
+    let unchanged = match req.headers().get(header::IF_NONE_MATCH) {
+        Some(value) => value
+            .to_str()
+            .map(|tags| {
+                tags.split(',').map(str::trim).any(|tag| {
+                    // If-None-Match uses the weak comparison, so W/"n" matches "n" too
+                    let tag = tag.strip_prefix("W/").unwrap_or(tag);
+                    tag == "*" || tag == etag.to_string()
+                })
+            })
+            .unwrap_or(false),
+        None => false,
+    };
+    if unchanged {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(header::ETag(etag))
+            .finish());
+    }
+    Ok(HttpResponse::Ok()
+        .insert_header(header::ETag(etag))
+        .json(&*lobby))
+}
+
+#[get("/games/{id}/pgn")]
+async fn get_pgn(
+    lobbies: web::Data<specs::LobbyRegistry>,
+    repository: web::Data<dyn GameRepository>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let lobby = find_lobby(&lobbies, &repository, &path).await?;
+    let pgn = lock_lobby(&lobby).to_pgn();
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-chess-pgn")
+        .body(pgn))
+}
+
+/** Finished games, most recent first, including those from before the last restart.
+ */
+#[get("/games")]
+async fn get_finished_games(
+    repository: web::Data<dyn GameRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let repository = repository.clone();
+    let games = storage::blocking(move || repository.list_finished()).await?;
+    Ok(HttpResponse::Ok().json(games))
+}
+
+#[get("/games/{id}")]
+async fn get_game(
+    repository: web::Data<dyn GameRepository>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    match saved_game(&repository, &path).await? {
+        Some(game) => Ok(HttpResponse::Ok().json(game)),
+        None => Err(ApiError::LobbyNotFound(path.into_inner())),
+    }
+}
+
+/** The lobby, or once its game is over, a copy of the game kept in the repository.
+ */
+async fn find_lobby(
+    lobbies: &specs::LobbyRegistry,
+    repository: &web::Data<dyn GameRepository>,
+    id: &str,
+) -> Result<specs::SharedLobby, ApiError> {
+    if let Some(lobby) = lobbies.get(id) {
+        return Ok(lobby);
+    }
+    match saved_game(repository, id).await? {
+        Some(game) => Ok(Arc::new(Mutex::new(game))),
+        None => Err(ApiError::LobbyNotFound(id.to_string())),
+    }
+}
+
+async fn saved_game(
+    repository: &web::Data<dyn GameRepository>,
+    id: &str,
+) -> Result<Option<chess_specs::ChessGame>, StorageError> {
+    let repository = repository.clone();
+    let id = id.to_string();
+    storage::blocking(move || repository.get(&id)).await
+}
+
+/** Unix timestamp in seconds.
+ */
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/** Unix timestamp in milliseconds, what game clocks run on.
+ */
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/** Draws the colours of a lobby created with a random one.
+ */
+fn coin_flip() -> bool {
+    OsRng.next_u32() & 1 == 1
 }
 
 struct AppStateWithCounter {
@@ -118,10 +432,41 @@ async fn example_get(data: web::Data<AppStateWithCounter>) -> String {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let repository = storage::open_from_env()
+        .map_err(|err| std::io::Error::other(err.to_string()))?;
+    // Finished games stay in the repository and are served from there
+    let saved_games = repository
+        .list_active()
+        .map_err(|err| std::io::Error::other(err.to_string()))?;
+    let accounts: Arc<dyn AccountRepository> = repository.clone();
+    let accounts: web::Data<dyn AccountRepository> = web::Data::from(accounts);
+    let seats: Arc<dyn SeatRepository> = repository.clone();
+    let repository: Arc<dyn GameRepository> = repository;
+    let repository: web::Data<dyn GameRepository> = web::Data::from(repository);
+    let seats = web::Data::new(
+        Seats::load(seats).map_err(|err| std::io::Error::other(err.to_string()))?,
+    );
+    let sessions = web::Data::new(Sessions::default());
+
     // Note: web::Data created _outside_ HttpServer::new closure
-    let lobbies = web::Data::new(specs::LobbiesMutex {
-        lobbies: Mutex::new(vec![]),
-    });
+    let lobbies = web::Data::new(specs::LobbyRegistry::new(saved_games));
+
+    let events = web::Data::new(LobbyEvents::default());
+
+    // Games left waiting on the computer's move when the server stopped
+    for lobby in lobbies.all() {
+        actix_web::rt::spawn(computer::reply(
+            lobby,
+            lobbies.clone(),
+            events.clone(),
+            repository.clone(),
+        ));
+    }
+    actix_web::rt::spawn(clocks::watch_flags(
+        lobbies.clone(),
+        events.clone(),
+        repository.clone(),
+    ));
 
     let counter = web::Data::new(AppStateWithCounter {
         counter: Mutex::new(0),
@@ -137,19 +482,258 @@ async fn main() -> std::io::Result<()> {
         .allowed_methods(vec!["GET", "POST"])
         .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
         .allowed_header(http::header::CONTENT_TYPE)
+        .allowed_header(http::header::IF_NONE_MATCH)
+        .expose_headers(vec![http::header::ETAG])
         .max_age(3600);
         // move counter into the closure
         App::new()
             .wrap(cors)
             .app_data(counter.clone())
             .app_data(lobbies.clone()) // <- registers the created data
+            .app_data(events.clone())
+            .app_data(repository.clone())
+            .app_data(accounts.clone())
+            .app_data(sessions.clone())
+            .app_data(seats.clone())
+            .app_data(web::JsonConfig::default().error_handler(|err, _req| {
+                ApiError::InvalidRequest(err.to_string()).into()
+            }))
             .route("/", web::get().to(example_get))
             .service(new_lobby)
             .service(get_lobbies)
             .service(join_lobby)
-            .service(get_update)
+            .service(move_piece)
+            // Before get_lobby, which would take "stream" for an id
+            .service(sse::lobbies_stream)
+            .service(get_lobby)
+            .service(ws::lobby_ws)
+            .service(sse::lobby_stream)
+            .service(get_setups)
+            .service(get_pgn)
+            .service(import_pgn)
+            .service(get_finished_games)
+            .service(get_game)
+            .service(claim_win)
+            .service(lifecycle::leave_lobby)
+            .service(lifecycle::abort)
+            .service(lifecycle::resign)
+            .service(lifecycle::offer_draw)
+            .service(lifecycle::accept_draw)
+            .service(lifecycle::decline_draw)
+            .service(your_turn)
+            .service(auth::register)
+            .service(auth::login)
+            .service(auth::logout)
     })
     .bind(("127.0.0.1", 8090))? // cargo watch -x run
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+    use actix_web::test;
+    use chess_core::specs::{ChessGame, Credentials};
+    use storage::MemoryRepository;
+
+    /** The account, lobby and move routes over an empty in-memory repository. */
+    fn test_app() -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    > {
+        let repository = Arc::new(MemoryRepository::default());
+        let seats: Arc<dyn SeatRepository> = repository.clone();
+        let accounts: Arc<dyn AccountRepository> = repository.clone();
+        let repository: Arc<dyn GameRepository> = repository;
+        App::new()
+            .app_data(web::Data::new(specs::LobbyRegistry::default()))
+            .app_data(web::Data::new(LobbyEvents::default()))
+            .app_data(web::Data::from(repository))
+            .app_data(web::Data::from(accounts))
+            .app_data(web::Data::new(Sessions::default()))
+            .app_data(web::Data::new(Seats::load(seats).unwrap()))
+            .service(new_lobby)
+            .service(join_lobby)
+            .service(move_piece)
+            .service(get_lobbies)
+            .service(get_lobby)
+            .service(lifecycle::resign)
+            .service(auth::register)
+            .service(auth::login)
+    }
+
+    /** Opens a lobby and fills its second seat, returning the creator's seat. */
+    async fn start_game(
+        app: &impl actix_web::dev::Service<
+            actix_http::Request,
+            Response = ServiceResponse,
+            Error = actix_web::Error,
+        >,
+    ) -> Seat {
+        let request = test::TestRequest::post()
+            .uri("/new-lobby")
+            .set_json(NewLobby::default())
+            .to_request();
+        let seat: Seat = test::call_and_read_body_json(app, request).await;
+        let request = test::TestRequest::post()
+            .uri("/join-lobby")
+            .set_json(JoinLobby {
+                id: seat.game.id.clone(),
+                player: None,
+            })
+            .to_request();
+        let _: Seat = test::call_and_read_body_json(app, request).await;
+        seat
+    }
+
+    #[actix_web::test]
+    async fn lobby_etag_changes_with_each_move() {
+        let app = test::init_service(test_app()).await;
+        let seat = start_game(&app).await;
+
+        let uri = format!("/lobbies/{}", seat.game.id);
+        let response =
+            test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let game: ChessGame = test::read_body_json(response).await;
+
+        for tag in [etag.clone(), format!("W/{etag}"), format!("\"-1\", {etag}")] {
+            let request = test::TestRequest::get()
+                .uri(&uri)
+                .insert_header((header::IF_NONE_MATCH, tag.as_str()))
+                .to_request();
+            let response = test::call_service(&app, request).await;
+            assert_eq!(response.status(), http::StatusCode::NOT_MODIFIED, "{tag}");
+        }
+
+        let request = test::TestRequest::post()
+            .uri("/move")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", seat.token)))
+            .set_json(MoveChessPiece {
+                id: game.id.clone(),
+                from: None,
+                to: None,
+                promotion: None,
+                notation: Some("e4".to_string()),
+            })
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let request = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header((header::IF_NONE_MATCH, etag.as_str()))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_ne!(
+            response
+                .headers()
+                .get(header::ETAG)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            etag
+        );
+    }
+
+    #[actix_web::test]
+    async fn finished_games_leave_the_lobby_list() {
+        let app = test::init_service(test_app()).await;
+        let seat = start_game(&app).await;
+        let request = test::TestRequest::post()
+            .uri("/resign")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", seat.token)))
+            .set_json(LobbyAction {
+                id: seat.game.id.clone(),
+            })
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let request = test::TestRequest::get().uri("/lobbies").to_request();
+        let lobbies: Vec<ChessGame> = test::call_and_read_body_json(&app, request).await;
+        assert!(lobbies.is_empty());
+        let uri = format!("/lobbies/{}", seat.game.id);
+        let request = test::TestRequest::get().uri(&uri).to_request();
+        let game: ChessGame = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(game.state, LobbyState::Finished);
+    }
+
+    #[actix_web::test]
+    async fn guests_cannot_take_the_name_of_an_account() {
+        let app = test::init_service(test_app()).await;
+        let credentials = Credentials {
+            username: "alice".to_string(),
+            password: "correct horse".to_string(),
+        };
+        let request = test::TestRequest::post()
+            .uri("/user/register")
+            .set_json(&credentials)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::CREATED);
+
+        let request = test::TestRequest::post()
+            .uri("/new-lobby")
+            .set_json(NewLobby {
+                player: Some(" alice ".to_string()),
+                ..NewLobby::default()
+            })
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::CONFLICT);
+
+        let request = test::TestRequest::post()
+            .uri("/new-lobby")
+            .set_json(NewLobby {
+                player: Some("bob".to_string()),
+                ..NewLobby::default()
+            })
+            .to_request();
+        let seat: Seat = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(seat.game.player1, "bob");
+    }
+
+    #[actix_web::test]
+    async fn login_refuses_unknown_users_and_wrong_passwords_alike() {
+        let app = test::init_service(test_app()).await;
+        let request = test::TestRequest::post()
+            .uri("/user/register")
+            .set_json(Credentials {
+                username: "alice".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::CREATED);
+
+        for (username, password) in [("alice", "battery staple"), ("bob", "correct horse")] {
+            let request = test::TestRequest::post()
+                .uri("/user/login")
+                .set_json(Credentials {
+                    username: username.to_string(),
+                    password: password.to_string(),
+                })
+                .to_request();
+            let response = test::call_service(&app, request).await;
+            assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+            let body = test::read_body(response).await;
+            assert!(String::from_utf8_lossy(&body).contains("wrong username or password"));
+        }
+    }
+}
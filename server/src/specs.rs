@@ -1,121 +1,91 @@
-use crate::data::{Board, ChessBoard, Color, PieceKind};
-use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
+use actix_web::web;
+use chess_core::specs::ChessGame;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+use uuid::Uuid;
 
-#[derive(Deserialize, Serialize)]
-pub struct JoinLobby {
-    pub id: String,
-    pub player: String,
-}
-
-#[derive(Deserialize, Serialize)]
-pub struct MoveChessPiece {
-    pub id: String,
-    pub player: String,
-    pub from: (u8, u8),
-    pub to: (u8, u8),
-}
+use crate::storage::{self, GameRepository, StorageError};
 
-#[derive(Deserialize, Serialize, PartialEq, Clone, Debug, Default)]
-pub struct UpdateReq {
-    pub id: String,
-}
+/** A lobby, locked on its own so moves in different games don't wait for each other.
+ */
+pub(crate) type SharedLobby = Arc<Mutex<ChessGame>>;
 
-#[derive(Deserialize, Serialize, PartialEq, Clone, Debug, Default)]
-pub struct ChessGame {
-    pub id: String,
-    pub board: ChessBoard,
-    pub color_player_1: Color,
-    pub player1: String,
-    pub player2: String,
-    pub is_started: bool,
-    pub is_over: bool,
+/** Locks the lobby even if a panic poisoned it, so one broken game doesn't take the lobby
+ * list, the streams and the flag watcher down with it.
+ */
+pub(crate) fn lock_lobby(lobby: &SharedLobby) -> MutexGuard<'_, ChessGame> {
+    lobby.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
-pub(crate) struct LobbiesMutex {
-    pub(crate) lobbies: Mutex<Vec<ChessGame>>,
+/** Every lobby, indexed by id. The map is only write-locked to add or drop a lobby; handlers clone the
+ * lobby's `Arc` out of it and then lock just that game.
+ */
+#[derive(Default)]
+pub(crate) struct LobbyRegistry {
+    lobbies: RwLock<HashMap<Uuid, SharedLobby>>,
 }
 
-impl ChessGame {
-    pub fn new(player1_color: Color) -> Self {
-        Self {
-            board: ChessBoard::new(gen_matrix()).clone(),
-            id: String::new(),
-            player1: String::new().clone(),
-            player2: String::new().clone(),
-            color_player_1: player1_color,
-            is_started: false,
-            is_over: false,
+impl LobbyRegistry {
+    pub(crate) fn new(games: Vec<ChessGame>) -> Self {
+        let registry = Self::default();
+        for game in games {
+            registry.insert(game);
         }
+        registry
     }
 
-    pub fn validify_move(&mut self, from: (u8, u8), to: (u8, u8)) -> bool {
-        let (x1, y1) = from;
-        let (x2, y2) = to;
-        let piece = self.board.board[x1 as usize][y1 as usize].clone();
-        if piece.is_none() {
-            return false;
-        }
-        let piece = piece.unwrap();
-        if piece.1 != self.board.turn {
-            return false;
-        }
-        if x2 > 7 || y2 > 7 {
-            return false;
-        }
-        let moves = self.board.get_moves(from);
-        if !moves.contains(&to) {
-            return false;
+    /** Adds the game under its id, replacing any lobby with the same id. Games whose id isn't a
+     * UUID can't be looked up and are left out.
+     */
+    pub(crate) fn insert(&self, game: ChessGame) {
+        if let Ok(id) = Uuid::parse_str(&game.id) {
+            let mut lobbies = self.lobbies.write().unwrap();
+            lobbies.insert(id, Arc::new(Mutex::new(game)));
         }
-        true
     }
 
-    /**
-     * Moves a piece from one square to another. Only verification is that the piece exists (is not none).
+    /** Saves the game on the blocking pool and gives it back. Called once the lobby is unlocked,
+     * so requests for the same game don't wait on the disk. A game that is over then leaves the
+     * registry and is read from the repository, the same as after a restart.
      */
-    pub fn move_piece(&mut self, from: (u8, u8), to: (u8, u8)) {
-        let (x1, y1) = from;
-        let (x2, y2) = to;
-        if !self.board.board[from.0 as usize][from.1 as usize].is_none() {
-            let piece = self.board.board[x1 as usize][y1 as usize].clone();
-            self.board.board[x1 as usize][y1 as usize] = None;
-            self.board.board[x2 as usize][y2 as usize] = piece;
+    pub(crate) async fn store(
+        &self,
+        repository: &web::Data<dyn GameRepository>,
+        game: ChessGame,
+    ) -> Result<ChessGame, StorageError> {
+        let repository = repository.clone();
+        let game = storage::blocking(move || repository.save(&game).map(|()| game)).await?;
+        if game.is_over {
+            if let Ok(id) = Uuid::parse_str(&game.id) {
+                let mut lobbies = self.lobbies.write().unwrap();
+                lobbies.remove(&id);
+            }
         }
+        Ok(game)
     }
 
-    pub fn load_new<'a>(&mut self) {
-        self.board.board[0][0] = Some((PieceKind::Rook, Color::White));
-        self.board.board[1][0] = Some((PieceKind::Knight, Color::White));
-        self.board.board[2][0] = Some((PieceKind::Bishop, Color::White));
-        self.board.board[3][0] = Some((PieceKind::Queen, Color::White));
-        self.board.board[4][0] = Some((PieceKind::King, Color::White));
-        self.board.board[5][0] = Some((PieceKind::Bishop, Color::White));
-        self.board.board[6][0] = Some((PieceKind::Knight, Color::White));
-        self.board.board[7][0] = Some((PieceKind::Rook, Color::White));
-        for n in 0..8 {
-            self.board.board[n][1] = Some((PieceKind::Pawn, Color::White));
-        }
+    pub(crate) fn get(&self, id: &str) -> Option<SharedLobby> {
+        let id = Uuid::parse_str(id).ok()?;
+        let lobbies = self.lobbies.read().unwrap();
+        lobbies.get(&id).cloned()
+    }
 
-        self.board.board[0][7] = Some((PieceKind::Rook, Color::Black));
-        self.board.board[1][7] = Some((PieceKind::Knight, Color::Black));
-        self.board.board[2][7] = Some((PieceKind::Bishop, Color::Black));
-        self.board.board[3][7] = Some((PieceKind::Queen, Color::Black));
-        self.board.board[4][7] = Some((PieceKind::King, Color::Black));
-        self.board.board[5][7] = Some((PieceKind::Bishop, Color::Black));
-        self.board.board[6][7] = Some((PieceKind::Knight, Color::Black));
-        self.board.board[7][7] = Some((PieceKind::Rook, Color::Black));
-        for n in 0..8 {
-            self.board.board[n][6] = Some((PieceKind::Pawn, Color::Black));
-        }
+    /** Every lobby, in no particular order.
+     */
+    pub(crate) fn all(&self) -> Vec<SharedLobby> {
+        let lobbies = self.lobbies.read().unwrap();
+        lobbies.values().cloned().collect()
     }
-}
 
-fn gen_matrix() -> Board {
-    let mut matrix: Board = vec![];
-    for _ in 0..8 {
-        let mut row: Vec<Option<(PieceKind, Color)>> = vec![];
-        row.resize(8, None);
-        matrix.push(row);
+    /** A copy of every lobby, oldest first.
+     */
+    pub(crate) fn snapshot(&self) -> Vec<ChessGame> {
+        let mut games: Vec<ChessGame> = self
+            .all()
+            .iter()
+            .map(|lobby| lock_lobby(lobby).clone())
+            .collect();
+        games.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        games
     }
-    matrix
 }
@@ -0,0 +1,94 @@
+use actix_web::{get, web, HttpResponse};
+use chess_core::specs::LobbyEvent;
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::HashMap;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::errors::ApiError;
+use crate::events::LobbyEvents;
+use crate::specs::{lock_lobby, LobbyRegistry};
+use crate::storage::GameRepository;
+
+/** Server-Sent Events version of the WebSocket channel, for when WebSockets are blocked.
+ * Each event is a `data:` line holding the JSON of a [`LobbyEvent`], whose `type` field says
+ * what happened.
+ */
+#[get("/lobbies/{id}/stream")]
+async fn lobby_stream(
+    lobbies: web::Data<LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+    repository: web::Data<dyn GameRepository>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let lobby = crate::find_lobby(&lobbies, &repository, &id).await?;
+    let receiver = events.subscribe(&id);
+    let game = lock_lobby(&lobby).clone();
+    // Nothing happens in a game once it is over, the snapshot is all there is to send
+    let receiver = (!game.is_over).then_some(receiver);
+    Ok(event_stream(vec![LobbyEvent::Snapshot { game }], receiver))
+}
+
+/** Events of every lobby, starting with a snapshot of each existing one, so the lobby list
+ * can stay up to date.
+ */
+#[get("/lobbies/stream")]
+async fn lobbies_stream(
+    lobbies: web::Data<LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+) -> HttpResponse {
+    let receiver = events.subscribe_all();
+    let snapshots = lobbies
+        .snapshot()
+        .into_iter()
+        .map(|game| LobbyEvent::Snapshot { game })
+        .collect();
+    event_stream(snapshots, Some(receiver))
+}
+
+/** Sends the snapshots, then the live events if there is a receiver. The receiver has to be
+ * subscribed before the snapshots are taken so no event falls in between; the events it holds
+ * that are older than their lobby's snapshot are skipped.
+ */
+fn event_stream(
+    first: Vec<LobbyEvent>,
+    receiver: Option<broadcast::Receiver<LobbyEvent>>,
+) -> HttpResponse {
+    let versions: HashMap<String, u64> = first
+        .iter()
+        .map(|event| (event.game().id.clone(), event.game().version))
+        .collect();
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(
+            stream::iter(first)
+                .chain(live_events(receiver).filter(move |event| {
+                    let game = event.game();
+                    let stale = versions
+                        .get(&game.id)
+                        .is_some_and(|version| game.version < *version);
+                    future::ready(!stale)
+                }))
+                .map(|event| {
+                    let json = serde_json::to_string(&event).expect("events always serialize");
+                    Ok::<_, actix_web::Error>(web::Bytes::from(format!("data: {json}\n\n")))
+                }),
+        )
+}
+
+fn live_events(
+    receiver: Option<broadcast::Receiver<LobbyEvent>>,
+) -> impl Stream<Item = LobbyEvent> {
+    stream::unfold(receiver, |receiver| async move {
+        let mut receiver = receiver?;
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((event, Some(receiver))),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
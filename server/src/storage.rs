@@ -0,0 +1,384 @@
+use actix_web::error::BlockingError;
+use actix_web::web;
+use chess_core::specs::ChessGame;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub(crate) struct StorageError(pub(crate) String);
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Storage error: {}", self.0)
+    }
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> Self {
+        StorageError(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(err: serde_json::Error) -> Self {
+        StorageError(err.to_string())
+    }
+}
+
+impl From<BlockingError> for StorageError {
+    fn from(err: BlockingError) -> Self {
+        StorageError(err.to_string())
+    }
+}
+
+/** Runs a repository call on the blocking pool, so the async workers never wait on the disk.
+ */
+pub(crate) async fn blocking<T, F>(call: F) -> Result<T, StorageError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, StorageError> + Send + 'static,
+{
+    web::block(call).await?
+}
+
+/** Where lobbies and games are kept between restarts. The handlers work on the lobbies held in
+ * memory and save a game here every time it changes.
+ */
+pub(crate) trait GameRepository: Send + Sync {
+    /** Creates the game or replaces the one with the same id, unless that one has a later
+     * version: saves of the same game may run concurrently and finish out of order.
+     */
+    fn save(&self, game: &ChessGame) -> Result<(), StorageError>;
+    fn get(&self, id: &str) -> Result<Option<ChessGame>, StorageError>;
+    /** Games still being played, oldest first.
+     */
+    fn list_active(&self) -> Result<Vec<ChessGame>, StorageError>;
+    /** Finished games, most recent first.
+     */
+    fn list_finished(&self) -> Result<Vec<ChessGame>, StorageError>;
+}
+
+/** A registered player. Only the Argon2 hash of the password is kept.
+ */
+#[derive(Clone, Debug)]
+pub(crate) struct Account {
+    pub(crate) username: String,
+    pub(crate) password_hash: String,
+    pub(crate) created_at: u64,
+}
+
+pub(crate) trait AccountRepository: Send + Sync {
+    /** Adds the account, `false` when the username is already taken.
+     */
+    fn create_account(&self, account: &Account) -> Result<bool, StorageError>;
+    fn get_account(&self, username: &str) -> Result<Option<Account>, StorageError>;
+}
+
+/** Who holds a seat of a lobby: whoever has the token, and the account that took it, if any.
+ */
+#[derive(Clone, Debug)]
+pub(crate) struct SeatHolder {
+    pub(crate) lobby_id: String,
+    /** 0 for `player1`, 1 for `player2`. */
+    pub(crate) seat: usize,
+    pub(crate) token: String,
+    pub(crate) account: Option<String>,
+}
+
+pub(crate) trait SeatRepository: Send + Sync {
+    fn save_seat(&self, holder: &SeatHolder) -> Result<(), StorageError>;
+    fn delete_seat(&self, lobby_id: &str, seat: usize) -> Result<(), StorageError>;
+    fn list_seats(&self) -> Result<Vec<SeatHolder>, StorageError>;
+}
+
+/** Everything the server stores, as returned by [`open_from_env`].
+ */
+pub(crate) trait Repository: GameRepository + AccountRepository + SeatRepository {}
+
+impl<T: GameRepository + AccountRepository + SeatRepository> Repository for T {}
+
+/** Keeps the games and accounts for as long as the server runs.
+ */
+#[derive(Default)]
+pub(crate) struct MemoryRepository {
+    games: Mutex<HashMap<String, ChessGame>>,
+    accounts: Mutex<HashMap<String, Account>>,
+    seats: Mutex<HashMap<(String, usize), SeatHolder>>,
+}
+
+impl MemoryRepository {
+    /** The games matching `filter`, oldest first.
+     */
+    fn sorted(&self, filter: impl Fn(&ChessGame) -> bool) -> Vec<ChessGame> {
+        let games = self.games.lock().unwrap();
+        let mut list: Vec<ChessGame> = games
+            .values()
+            .filter(|game| filter(game))
+            .cloned()
+            .collect();
+        list.sort_by_key(|game| game.created_at);
+        list
+    }
+}
+
+impl GameRepository for MemoryRepository {
+    fn save(&self, game: &ChessGame) -> Result<(), StorageError> {
+        let mut games = self.games.lock().unwrap();
+        let newer = games
+            .get(&game.id)
+            .is_some_and(|saved| saved.version > game.version);
+        if !newer {
+            games.insert(game.id.clone(), game.clone());
+        }
+        Ok(())
+    }
+
+    fn get(&self, id: &str) -> Result<Option<ChessGame>, StorageError> {
+        let games = self.games.lock().unwrap();
+        Ok(games.get(id).cloned())
+    }
+
+    fn list_active(&self) -> Result<Vec<ChessGame>, StorageError> {
+        Ok(self.sorted(|game| !game.is_over))
+    }
+
+    fn list_finished(&self) -> Result<Vec<ChessGame>, StorageError> {
+        let mut list = self.sorted(|game| game.is_over);
+        list.reverse();
+        Ok(list)
+    }
+}
+
+impl AccountRepository for MemoryRepository {
+    fn create_account(&self, account: &Account) -> Result<bool, StorageError> {
+        let mut accounts = self.accounts.lock().unwrap();
+        if accounts.contains_key(&account.username) {
+            return Ok(false);
+        }
+        accounts.insert(account.username.clone(), account.clone());
+        Ok(true)
+    }
+
+    fn get_account(&self, username: &str) -> Result<Option<Account>, StorageError> {
+        let accounts = self.accounts.lock().unwrap();
+        Ok(accounts.get(username).cloned())
+    }
+}
+
+impl SeatRepository for MemoryRepository {
+    fn save_seat(&self, holder: &SeatHolder) -> Result<(), StorageError> {
+        let mut seats = self.seats.lock().unwrap();
+        seats.insert((holder.lobby_id.clone(), holder.seat), holder.clone());
+        Ok(())
+    }
+
+    fn delete_seat(&self, lobby_id: &str, seat: usize) -> Result<(), StorageError> {
+        let mut seats = self.seats.lock().unwrap();
+        seats.remove(&(lobby_id.to_string(), seat));
+        Ok(())
+    }
+
+    fn list_seats(&self) -> Result<Vec<SeatHolder>, StorageError> {
+        let seats = self.seats.lock().unwrap();
+        Ok(seats.values().cloned().collect())
+    }
+}
+
+/** Games stored as JSON in an SQLite file, next to the accounts and seats, no database server
+ * needed.
+ */
+pub(crate) struct SqliteRepository {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteRepository {
+    pub(crate) fn open(path: &str) -> Result<Self, StorageError> {
+        let connection = Connection::open(path)?;
+        // Readers don't block the writer, and a commit costs one write to the log
+        connection.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS games (
+                id TEXT PRIMARY KEY,
+                created_at INTEGER NOT NULL,
+                is_over INTEGER NOT NULL,
+                data TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS games_is_over ON games (is_over, created_at);
+            CREATE TABLE IF NOT EXISTS accounts (
+                username TEXT PRIMARY KEY,
+                password_hash TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS seats (
+                lobby_id TEXT NOT NULL,
+                seat INTEGER NOT NULL,
+                token TEXT NOT NULL,
+                account TEXT,
+                PRIMARY KEY (lobby_id, seat)
+            );",
+        )?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn query(&self, sql: &str) -> Result<Vec<ChessGame>, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(sql)?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+        let mut games = vec![];
+        for data in rows {
+            games.push(serde_json::from_str(&data?)?);
+        }
+        Ok(games)
+    }
+}
+
+impl GameRepository for SqliteRepository {
+    fn save(&self, game: &ChessGame) -> Result<(), StorageError> {
+        let data = serde_json::to_string(game)?;
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT INTO games (id, created_at, is_over, data) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (id) DO UPDATE SET is_over = excluded.is_over, data = excluded.data
+             WHERE json_extract(excluded.data, '$.version')
+                 >= json_extract(games.data, '$.version')",
+            params![game.id, game.created_at as i64, game.is_over, data],
+        )?;
+        Ok(())
+    }
+
+    fn get(&self, id: &str) -> Result<Option<ChessGame>, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let data: Option<String> = connection
+            .query_row("SELECT data FROM games WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        match data {
+            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn list_active(&self) -> Result<Vec<ChessGame>, StorageError> {
+        self.query("SELECT data FROM games WHERE is_over = 0 ORDER BY created_at, rowid")
+    }
+
+    fn list_finished(&self) -> Result<Vec<ChessGame>, StorageError> {
+        self.query("SELECT data FROM games WHERE is_over = 1 ORDER BY created_at DESC, rowid DESC")
+    }
+}
+
+impl AccountRepository for SqliteRepository {
+    fn create_account(&self, account: &Account) -> Result<bool, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let inserted = connection.execute(
+            "INSERT OR IGNORE INTO accounts (username, password_hash, created_at) VALUES (?1, ?2, ?3)",
+            params![account.username, account.password_hash, account.created_at as i64],
+        )?;
+        Ok(inserted == 1)
+    }
+
+    fn get_account(&self, username: &str) -> Result<Option<Account>, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let account = connection
+            .query_row(
+                "SELECT username, password_hash, created_at FROM accounts WHERE username = ?1",
+                [username],
+                |row| {
+                    Ok(Account {
+                        username: row.get(0)?,
+                        password_hash: row.get(1)?,
+                        created_at: row.get::<_, i64>(2)? as u64,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(account)
+    }
+}
+
+impl SeatRepository for SqliteRepository {
+    fn save_seat(&self, holder: &SeatHolder) -> Result<(), StorageError> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT OR REPLACE INTO seats (lobby_id, seat, token, account) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                holder.lobby_id,
+                holder.seat as i64,
+                holder.token,
+                holder.account
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_seat(&self, lobby_id: &str, seat: usize) -> Result<(), StorageError> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "DELETE FROM seats WHERE lobby_id = ?1 AND seat = ?2",
+            params![lobby_id, seat as i64],
+        )?;
+        Ok(())
+    }
+
+    fn list_seats(&self) -> Result<Vec<SeatHolder>, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement =
+            connection.prepare("SELECT lobby_id, seat, token, account FROM seats")?;
+        let rows = statement.query_map([], |row| {
+            Ok(SeatHolder {
+                lobby_id: row.get(0)?,
+                seat: row.get::<_, i64>(1)? as usize,
+                token: row.get(2)?,
+                account: row.get(3)?,
+            })
+        })?;
+        let mut seats = vec![];
+        for holder in rows {
+            seats.push(holder?);
+        }
+        Ok(seats)
+    }
+}
+
+/** The SQLite file named by the `CHESS_DB` environment variable (`chess.db` by default), or
+ * memory only when it is set to `memory`.
+ */
+pub(crate) fn open_from_env() -> Result<Arc<dyn Repository>, StorageError> {
+    let path = std::env::var("CHESS_DB").unwrap_or_else(|_| "chess.db".to_string());
+    if path == "memory" {
+        Ok(Arc::new(MemoryRepository::default()))
+    } else {
+        Ok(Arc::new(SqliteRepository::open(&path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess_core::data::Color;
+
+    fn game(version: u64) -> ChessGame {
+        let mut game = ChessGame::new(Color::White);
+        game.id = "game".to_string();
+        game.version = version;
+        game
+    }
+
+    #[test]
+    fn saves_out_of_order_keep_the_latest_version() {
+        let memory = MemoryRepository::default();
+        let sqlite = SqliteRepository::open(":memory:").unwrap();
+        for repository in [&memory as &dyn GameRepository, &sqlite] {
+            repository.save(&game(2)).unwrap();
+            repository.save(&game(1)).unwrap();
+            assert_eq!(repository.get("game").unwrap().unwrap().version, 2);
+            repository.save(&game(3)).unwrap();
+            assert_eq!(repository.get("game").unwrap().unwrap().version, 3);
+        }
+    }
+}
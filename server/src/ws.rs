@@ -0,0 +1,75 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_ws::Message;
+use chess_core::specs::LobbyEvent;
+use futures::StreamExt;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::LobbyEvents;
+use crate::specs::{lock_lobby, LobbyRegistry};
+use crate::storage::GameRepository;
+
+/** Pushes every [`LobbyEvent`] of the lobby as a JSON text message, starting with a snapshot.
+ * Anyone can connect, players and spectators alike; messages from the client are ignored.
+ */
+#[get("/lobbies/{id}/ws")]
+async fn lobby_ws(
+    lobbies: web::Data<LobbyRegistry>,
+    events: web::Data<LobbyEvents>,
+    repository: web::Data<dyn GameRepository>,
+    path: web::Path<String>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = path.into_inner();
+    let lobby = crate::find_lobby(&lobbies, &repository, &id).await?;
+    // Subscribed before the snapshot is taken so no event can fall in between, the events
+    // older than the snapshot are skipped instead
+    let mut receiver = events.subscribe(&id);
+    let game = lock_lobby(&lobby).clone();
+    let version = game.version;
+    // Nothing happens in a game once it is over, the snapshot is all there is to send
+    let over = game.is_over;
+    let snapshot = LobbyEvent::Snapshot { game };
+    let (response, mut session, mut messages) = actix_ws::handle(&req, body)?;
+
+    actix_web::rt::spawn(async move {
+        if send(&mut session, &snapshot).await.is_err() {
+            return;
+        }
+        if over {
+            let _ = session.close(None).await;
+            return;
+        }
+        loop {
+            tokio::select! {
+                event = receiver.recv() => match event {
+                    Ok(event) if event.game().version < version => continue,
+                    Ok(event) => {
+                        if send(&mut session, &event).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                message = messages.next() => match message {
+                    Some(Ok(Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
+
+async fn send(session: &mut actix_ws::Session, event: &LobbyEvent) -> Result<(), actix_ws::Closed> {
+    let text = serde_json::to_string(event).expect("events always serialize");
+    session.text(text).await
+}